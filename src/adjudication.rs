@@ -0,0 +1,211 @@
+use std::fmt;
+use chess::{Board, Color};
+use crate::arena::{material, material_balance, Outcome};
+
+/// Rules for stopping an automated game before it is played out. The
+/// position is judged on material, counted in pawns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Adjudication {
+    /// Longest game in half moves, after which it is drawn
+    pub max_plies: usize,
+    /// At `max_plies`, award the game to whoever is ahead on material instead
+    pub material_tie_break: bool,
+    /// Material lead that wins the game once held for `win_plies` half moves
+    pub win_margin: Option<i32>,
+    pub win_plies: usize,
+    /// Draw once neither side has more than this much material besides the king
+    pub draw_material: Option<i32>,
+    /// Draw once the material balance has stayed within this margin for `draw_plies` half moves
+    pub draw_margin: Option<i32>,
+    pub draw_plies: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            max_plies: 2000,
+            material_tie_break: false,
+            win_margin: None,
+            win_plies: 10,
+            draw_material: None,
+            draw_margin: None,
+            draw_plies: 100,
+        }
+    }
+}
+
+/// Why a game ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    /// Threefold repetition or the fifty move rule
+    DrawClaim,
+    /// One side held a decisive material lead
+    WinAdjudicated,
+    /// Too little material left for either side to win
+    LowMaterial,
+    /// The material balance stayed level for too long
+    LevelMaterial,
+    /// Reached the maximum game length
+    MaxLength,
+    /// A player ran out of time
+    TimeForfeit,
+    Resignation,
+    /// The players agreed a draw
+    DrawAgreed,
+    /// A player couldn't carry on, e.g. its input closed or it made an illegal move
+    Abandoned,
+}
+
+impl Termination {
+    /// Value for the PGN Termination tag, which only has a few standard ones;
+    /// the detailed reason goes in a comment after the moves
+    pub fn pgn_tag(&self) -> &'static str {
+        match self {
+            Termination::Checkmate | Termination::Stalemate | Termination::DrawClaim
+                | Termination::Resignation | Termination::DrawAgreed => "normal",
+            Termination::WinAdjudicated | Termination::LowMaterial | Termination::LevelMaterial
+                | Termination::MaxLength => "adjudication",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Abandoned => "abandoned",
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::DrawClaim => "draw claimed",
+            Termination::WinAdjudicated => "adjudicated win",
+            Termination::LowMaterial => "insufficient material",
+            Termination::LevelMaterial => "adjudicated draw",
+            Termination::MaxLength => "maximum length",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::Resignation => "resignation",
+            Termination::DrawAgreed => "draw agreed",
+            Termination::Abandoned => "abandoned",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Tracks a game move by move against a set of [`Adjudication`] rules
+pub struct Adjudicator<'a> {
+    rules: &'a Adjudication,
+    /// Side holding the winning margin, as the sign of the balance, and for how long
+    leader: i32,
+    lead_plies: usize,
+    level_plies: usize,
+}
+
+impl<'a> Adjudicator<'a> {
+    pub fn new(rules: &'a Adjudication) -> Adjudicator<'a> {
+        Adjudicator { rules, leader: 0, lead_plies: 0, level_plies: 0 }
+    }
+
+    /// Judge the position after a move, returning the result if the game should stop
+    pub fn update(&mut self, board: &Board) -> Option<(Outcome, Termination)> {
+        let white = material(board, Color::White);
+        let black = material(board, Color::Black);
+        let balance = white - black;
+
+        if let Some(margin) = self.rules.win_margin {
+            if balance.abs() >= margin {
+                if balance.signum() == self.leader {
+                    self.lead_plies += 1;
+                } else {
+                    self.leader = balance.signum();
+                    self.lead_plies = 1;
+                }
+                if self.lead_plies >= self.rules.win_plies {
+                    return Some((Outcome::from_material(balance), Termination::WinAdjudicated));
+                }
+            } else {
+                self.leader = 0;
+                self.lead_plies = 0;
+            }
+        }
+
+        if let Some(limit) = self.rules.draw_material {
+            if white <= limit && black <= limit {
+                return Some((Outcome::Draw, Termination::LowMaterial));
+            }
+        }
+
+        if let Some(margin) = self.rules.draw_margin {
+            if balance.abs() <= margin {
+                self.level_plies += 1;
+                if self.level_plies >= self.rules.draw_plies {
+                    return Some((Outcome::Draw, Termination::LevelMaterial));
+                }
+            } else {
+                self.level_plies = 0;
+            }
+        }
+        None
+    }
+
+    /// Result for a game stopped at the maximum length
+    pub fn finish(&self, board: &Board) -> (Outcome, Termination) {
+        let outcome = if self.rules.material_tie_break {
+            Outcome::from_material(material_balance(board))
+        } else {
+            Outcome::Draw
+        };
+        (outcome, Termination::MaxLength)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use chess::Board;
+    use crate::adjudication::{Adjudication, Adjudicator, Termination};
+    use crate::arena::Outcome;
+
+    #[test]
+    fn default_never_adjudicates() {
+        let rules = Adjudication::default();
+        let mut adjudicator = Adjudicator::new(&rules);
+        let board = Board::from_str("4k3/8/8/8/8/8/8/QQQ1K3 w - - 0 1").unwrap();
+        for _ in 0..1000 {
+            assert_eq!(adjudicator.update(&board), None);
+        }
+        assert_eq!(adjudicator.finish(&board), (Outcome::Draw, Termination::MaxLength));
+    }
+
+    #[test]
+    fn lead_must_be_held() {
+        let rules = Adjudication { win_margin: Some(5), win_plies: 3, ..Default::default() };
+        let mut adjudicator = Adjudicator::new(&rules);
+        let winning = Board::from_str("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert_eq!(adjudicator.update(&winning), None);
+        assert_eq!(adjudicator.update(&Board::default()), None);
+        assert_eq!(adjudicator.update(&winning), None);
+        assert_eq!(adjudicator.update(&winning), None);
+        assert_eq!(adjudicator.update(&winning), Some((Outcome::WhiteWins, Termination::WinAdjudicated)));
+    }
+
+    #[test]
+    fn draws() {
+        let rules = Adjudication { draw_material: Some(3), ..Default::default() };
+        let bare = Board::from_str("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert_eq!(Adjudicator::new(&rules).update(&bare), Some((Outcome::Draw, Termination::LowMaterial)));
+
+        let rules = Adjudication { draw_margin: Some(0), draw_plies: 2, ..Default::default() };
+        let mut adjudicator = Adjudicator::new(&rules);
+        assert_eq!(adjudicator.update(&Board::default()), None);
+        assert_eq!(adjudicator.update(&Board::default()), Some((Outcome::Draw, Termination::LevelMaterial)));
+    }
+
+    #[test]
+    fn tie_break_at_max_length() {
+        let rules = Adjudication { material_tie_break: true, ..Default::default() };
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(Adjudicator::new(&rules).finish(&board), (Outcome::WhiteWins, Termination::MaxLength));
+    }
+}
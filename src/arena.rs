@@ -1,71 +1,104 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{create_dir_all, File};
 use std::env;
-use candle_core::{Device, DType, Tensor, NdArray};
+use std::time::Instant;
+use candle_core::{Tensor, Var};
 use candle_nn::{VarMap};
-use chess::{Game, GameResult};
+use chess::{Board, ChessMove, Color, Game, GameResult, Piece};
 use chrono::Datelike;
-use rand::distributions::WeightedIndex;
 use rand::prelude::*;
+use crate::adjudication::{Adjudication, Adjudicator, Termination};
+use crate::baseline::{benchmarks, BENCHMARK_DEPTH};
+use crate::clock::{can_mate, Clock, TimeControl};
+use crate::diversity::Diversity;
+use crate::genetic::crossover::{Crossover, Uniform};
+use crate::genetic::mutation::Mutation;
+use crate::genetic::selection::{ranking, Selection};
+use crate::metrics::{append, EpochMetrics, METRICS_FILE};
 use crate::nn::ChessNet;
-use crate::player::Player;
+use crate::openings;
+use crate::player::{Action, Player};
+use crate::rating::RatingsDb;
+use crate::time_manager::SearchLimits;
+
+/// Offers, bad accepts and refused take-backs a player may make on one turn before it has to move
+const MAX_ACTIONS: usize = 8;
 
 impl  ChessNet {
-    fn merge(&self, other: &ChessNet, scores: [u64; 2]) -> ChessNet {
-        let mutation_threshold = u32::MAX / 10;
-        let mut varmap = VarMap::new();
-        for layer in ["c1", "t1"] {
-            let my_vars = self.get_weights_and_biases(layer);
-            let other_vars = other.get_weights_and_biases(layer);
-            let dist = WeightedIndex::new(scores).unwrap();
-
-            let w_dim = my_vars.0.dims4().unwrap();
-            let mut new_weight: Vec<Vec<Vec<Vec<f64>>>> = vec![vec![vec![vec![0f64; w_dim.3]; w_dim.2]; w_dim.1]; w_dim.0];
-            for i in 0..w_dim.0 {
-                for j in 0..w_dim.1 {
-                    for k in 0..w_dim.2 {
-                        for l in 0..w_dim.3 {
-                            let inherit_weight: f64 = match thread_rng().sample(&dist) {
-                                1 => my_vars.0
-                                    .get(i).expect("i")
-                                    .get(j).expect("j")
-                                    .get(k).expect("k")
-                                    .get(l).expect("l")
-                                    .to_scalar().expect("scalar"),
-                                0 => other_vars.0
-                                    .get(i).expect("i")
-                                    .get(j).expect("j")
-                                    .get(k).expect("k")
-                                    .get(l).expect("l")
-                                    .to_scalar().expect("scalar"),
-                                _ => panic!("Got something else")
-                            };
-                            let mutation = match thread_rng().next_u32() {
-                                v if v < mutation_threshold => 0.5,
-                                v if v > u32::MAX - mutation_threshold => 2.,
-                                _ => 1.,
-                            };
-                            new_weight[i][j][k][l] = inherit_weight * mutation;
-                        }
-                    }
-                }
-            }
-            varmap.get(new_weight.shape().unwrap(), &*(layer.to_string() + ".weight"), Default::default(), DType::F64, &Device::Cpu).expect("TODO: panic message");
-            varmap.set_one(layer.to_string() + ".weight", Tensor::new(new_weight, &Device::Cpu).unwrap()).expect("Error setting weight");
-
-            let b_dim = my_vars.1.dims1().unwrap();
-            let mut new_bias: Vec<f64> = vec![0f64; 2];
-            for i in 0..b_dim {
-                new_bias[i] = match thread_rng().sample(&dist) {
-                    1 => my_vars.1.get(i).unwrap().to_scalar().unwrap(),
-                    0 => other_vars.1.get(i).unwrap().to_scalar().unwrap(),
-                    _ => panic!("Got something else")
+    pub(crate) fn merge(&self, other: &ChessNet, scores: [f64; 2], config: &TrainConfig) -> ChessNet {
+        let (names, mine): (Vec<String>, Vec<Tensor>) = self.named_tensors().into_iter().unzip();
+        let theirs: Vec<Tensor> = other.named_tensors().into_iter().map(|(_, t)| t).collect();
+        // chance of inheriting from self, weighted towards whoever won the match
+        let bias = match scores[0] + scores[1] {
+            total if total > 0. => scores[0] / total,
+            _ => 0.5,
+        };
+        let child = config.crossover.cross(&mine, &theirs, bias)
+            .expect("Error crossing parents");
+
+        let varmap = VarMap::new();
+        let mut step_sizes = HashMap::new();
+        {
+            let mut data = varmap.data().lock().unwrap();
+            for (name, tensor) in names.into_iter().zip(child) {
+                // children start from the mean of their parents' step sizes
+                let mut step_size = match (self.step_sizes.get(&name), other.step_sizes.get(&name)) {
+                    (Some(mine), Some(theirs)) => (mine + theirs) / 2.,
+                    (Some(step), None) | (None, Some(step)) => *step,
+                    (None, None) => config.mutation.initial_step_size(),
                 };
+                let tensor = config.mutation.mutate(&tensor, &mut step_size)
+                    .expect("Error mutating tensor");
+                data.insert(name.clone(), Var::from_tensor(&tensor).expect("Error creating variable"));
+                step_sizes.insert(name, step_size);
             }
-            varmap.get(new_bias.shape().unwrap(), &*(layer.to_string() + ".bias"), Default::default(), DType::F64, &Device::Cpu).expect("TODO: panic message");
-            varmap.set_one(layer.to_string() + ".bias", Tensor::new(new_bias, &Device::Cpu).unwrap()).expect("Error setting bias");
         }
 
-        ChessNet::new(varmap)
+        let mut child = ChessNet::new(varmap);
+        child.step_sizes = step_sizes;
+        child
+    }
+}
+
+/// Genetic operator settings for an [`Arena`]
+#[derive(Debug)]
+pub struct TrainConfig {
+    pub crossover: Box<dyn Crossover>,
+    pub mutation: Mutation,
+    pub selection: Selection,
+    /// Number of top members carried into the next generation unchanged
+    pub elites: usize,
+    /// Number of past champions every member must also play, 0 to disable
+    pub hall_of_fame: usize,
+    /// Where the hall of fame is kept between runs, or None to start empty every run
+    pub hall_of_fame_file: Option<String>,
+    /// When games are stopped early
+    pub adjudication: Adjudication,
+    /// Clock every training game with this control
+    pub time_control: Option<TimeControl>,
+    /// Games each champion plays against every benchmark opponent, 0 to skip them
+    pub benchmark_games: usize,
+    /// Search depth of the minimax benchmark
+    pub benchmark_depth: u32,
+    /// Directory holding the run logs and the ratings database
+    pub dir: String,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            crossover: Box::new(Uniform),
+            mutation: Mutation::default(),
+            selection: Selection::default(),
+            elites: 0,
+            hall_of_fame: 0,
+            hall_of_fame_file: None,
+            adjudication: Adjudication::default(),
+            time_control: None,
+            benchmark_games: 0,
+            benchmark_depth: BENCHMARK_DEPTH,
+            dir: resources_dir(),
+        }
     }
 }
 
@@ -74,10 +107,15 @@ pub struct Arena {
     num_epochs: i32,
     members: Vec<ChessNet>,
     log_dir: String,
+    config: TrainConfig,
+    /// Most recent champions as (saved file, network), oldest first
+    hall_of_fame: Vec<(String, ChessNet)>,
+    /// Fixed opponents every member also plays, by name
+    pub sparring: Vec<(String, Box<dyn Player>)>,
 }
 
 impl Arena {
-    pub fn new(num_members: usize, num_epochs: i32) -> Arena {
+    pub fn new(num_members: usize, num_epochs: i32, config: TrainConfig) -> Arena {
         let mut members: Vec<ChessNet> = vec!();
         for _ in 0..num_members {
             let varmap = VarMap::new();
@@ -85,110 +123,513 @@ impl Arena {
         }
 
         let date = chrono::Utc::now();
-        let resources = env::var("HOME").expect("No HOME dir?") + "/.chessers";
         let log_dir = format!("{}/logs/{}_{:02}_{:02}",
-                              resources, date.year(), date.month(), date.day());
+                              config.dir, date.year(), date.month(), date.day());
         create_dir_all(&log_dir).expect("Error creating log directory");
 
+        let hall_of_fame = config.hall_of_fame_file.as_deref().map_or_else(Vec::new, |file| load_hall_of_fame(file, config.hall_of_fame));
         Arena {
             num_members,
             num_epochs,
             members,
             log_dir,
+            config,
+            hall_of_fame,
+            sparring: vec!(),
         }
     }
 
-    fn play_game(white: &ChessNet, black: &ChessNet) -> GameResult {
-        let mut game = Game::new();
-        for _ in 1..1000 {
-            game.make_move(white.make_move(&game.current_position()));
-            if check_game(&mut game) { break; };
-            game.make_move(black.make_move(&game.current_position()));
-            if check_game(&mut game) { break; };
-        }
-        game.result().unwrap()
+    fn play_game(&self, white: &dyn Player, black: &dyn Player) -> Outcome {
+        play_from(white, black, &[], &self.config.adjudication, self.config.time_control).outcome
     }
 
     pub(crate) fn train(&mut self) {
         // create champs.csv for logging champion index of each epoch
         let champ_file = format!("{}/champs.csv", &self.log_dir);
         let mut champ_writer = csv::Writer::from_path(&champ_file)
-            .expect(format!("Failed to open {} for writing", &champ_file).as_str());
-        champ_writer.write_record(&["Epoch", "Champ index", "Wins"])
+            .unwrap_or_else(|_| panic!("Failed to open {} for writing", &champ_file));
+        champ_writer.write_record(["Epoch", "Champ index", "Score"])
             .expect("TODO: panic message");
-        
+        let diversity_file = format!("{}/diversity.csv", &self.log_dir);
+        let mut diversity_writer = csv::Writer::from_path(&diversity_file)
+            .unwrap_or_else(|_| panic!("Failed to open {} for writing", &diversity_file));
+        diversity_writer.write_record(["Epoch", "Distance", "Agreement", "Unique games", "Games"])
+            .expect("Failed to write diversity header");
+        let metrics_file = format!("{}/{}", &self.log_dir, METRICS_FILE);
+        let mut ratings = RatingsDb::open(format!("{}/ratings.csv", self.config.dir));
         
         for epoch in 0..self.num_epochs {
-            let mut scores = vec![vec![1u64; self.num_members]; self.num_members];
-            for (i, member_white) in (&self.members).iter().enumerate() {
-                for (j, member_black) in (&self.members).iter().enumerate() {
-                    let result = Arena::play_game(&member_white, &member_black);
-                    match result {
-                        GameResult::WhiteCheckmates => scores[i][j] += 1,
-                        GameResult::BlackCheckmates => scores[j][i] += 1,
-                        _ => (),
-                    };
-                    // println!("Game {}, {}: {:?}", i, j, result);
+            let mut standings = Standings::new(self.num_members);
+            let mut games = Vec::new();
+            let mut metrics = EpochMetrics::new(epoch);
+            let start = Instant::now();
+            for (i, member_white) in self.members.iter().enumerate() {
+                for (j, member_black) in self.members.iter().enumerate() {
+                    if i == j {
+                        continue;
+                    }
+                    let game = play_from(member_white, member_black, &[], &self.config.adjudication, self.config.time_control);
+                    let outcome = game.outcome;
+                    metrics.record(&game);
+                    games.push(game.moves);
+                    standings.record(i, j, outcome);
+                    ratings.record(&self.member_file(epoch, i), &self.member_file(epoch, j), outcome.white_score());
                 }
             }
-            let totals = scores.iter().map(|row| row.iter().sum::<u64>());
-            let dist: WeightedIndex<u64> = WeightedIndex::new(totals.clone()).unwrap();
-            let mut new_members: Vec<ChessNet> = vec!();
-            for (i, member) in (&self.members).iter().enumerate() {
-                let j = dist.sample(&mut thread_rng());
-                let partner = &self.members[j];
-                new_members.push(member.merge(partner, [scores[i][j], scores[j][i]]));
-            };
-            self.members = new_members;
+            for (i, member) in self.members.iter().enumerate() {
+                self.play_outsiders(epoch, i, member, &mut standings, &mut ratings);
+            }
+            let fitness = standings.fitness();
             println!("Finished epoch {}", epoch);
-            println!("Scores: {:?}", scores);
-            println!("Wins: {:?}", scores.iter().flatten().filter(|&&v| v == 2).count());
+            println!("Scores: {:?}", standings.scores);
+            println!("White wins: {}, draws: {}, black wins: {}", standings.results[0], standings.results[1], standings.results[2]);
+            standings.print_colours();
+            let diversity = Diversity::measure(&self.members, &games);
+            println!("Diversity: distance {:.4}, agreement {:.1}%, {}/{} unique games",
+                     diversity.distance, 100. * diversity.agreement, diversity.unique_games, diversity.games);
+            diversity_writer.write_record(&[epoch.to_string(), diversity.distance.to_string(), diversity.agreement.to_string(),
+                                            diversity.unique_games.to_string(), diversity.games.to_string()])
+                .expect("Failed to write diversity");
+            diversity_writer.flush().expect("Failed to flush diversity");
+            // save the generation that just played, so champs.csv points at the real champion
             for (i, member) in self.members.iter().enumerate() {
-                member.save(format!("{}/{:04}_{:04}.safetensors", &self.log_dir, epoch, i));
+                member.save(self.member_file(epoch, i));
             }
-            let champ_id = totals.enumerate().max_by(|(_, value0), (_, value1)| value0.cmp(value1)).unwrap().0;
+            let champ_id = ranking(&fitness)[0];
             let champ = &self.members[champ_id];
-            let wins = self.evaluate(champ, champ_file.as_str());
-            self.log_champ(&mut champ_writer, epoch, champ_id, wins);
+            let (score, history_games) = self.evaluate(&self.member_file(epoch, champ_id), champ, champ_file.as_str(), &mut ratings);
+            metrics.benchmarks = self.benchmark(&self.member_file(epoch, champ_id), champ, &mut ratings);
+            // every game this epoch is one rating period
+            ratings.commit();
+            self.log_champ(&mut champ_writer, epoch, champ_id, score);
+            self.induct(self.member_file(epoch, champ_id));
+            metrics.fitness = fitness.clone();
+            metrics.champion = champ_id;
+            metrics.history_score = score;
+            metrics.history_games = history_games;
+            metrics.seconds = start.elapsed().as_secs_f64();
+            append(&metrics_file, &metrics);
+
+            if epoch + 1 < self.num_epochs {
+                self.members = self.breed(&standings.scores, &fitness);
+            }
         }
     }
 
-    fn evaluate(&self, champion: &ChessNet, champs_file: &str) -> i32 {
-        // Compare champion to the best from all previous epochs
+    fn member_file(&self, epoch: i32, index: usize) -> String {
+        format!("{}/{:04}_{:04}.safetensors", &self.log_dir, epoch, index)
+    }
+
+    /// Play every hall of fame member and sparring partner with both colours.
+    /// Only the member's colour totals are updated, since they aren't competing.
+    fn play_outsiders(&self, epoch: i32, index: usize, member: &ChessNet, standings: &mut Standings, ratings: &mut RatingsDb) {
+        let member_file = self.member_file(epoch, index);
+        let famers = self.hall_of_fame.iter().map(|(file, famer)| (file, famer as &dyn Player));
+        let partners = self.sparring.iter().map(|(name, partner)| (name, partner.as_ref()));
+        for (name, outsider) in famers.chain(partners) {
+            let outcome = self.play_game(member, outsider);
+            standings.white[index].add(outcome.white_score());
+            ratings.record(&member_file, name, outcome.white_score());
+            let outcome = self.play_game(outsider, member);
+            standings.black[index].add(1. - outcome.white_score());
+            ratings.record(name, &member_file, outcome.white_score());
+        }
+    }
+
+    /// Add a champion to the hall of fame, retiring the oldest once it is full
+    fn induct(&mut self, champ_file: String) {
+        if self.config.hall_of_fame == 0 {
+            return;
+        }
+        let champ = ChessNet::from_file(&champ_file);
+        // a run on the same day reuses the log directory, so a kept hall of fame needs its own copy
+        let champ_file = match &self.config.hall_of_fame_file {
+            Some(hof_file) => {
+                let dir = std::path::Path::new(hof_file).with_extension("");
+                create_dir_all(&dir).expect("Error creating hall of fame directory");
+                let copy = format!("{}/{}.safetensors", dir.display(), chrono::Utc::now().format("%Y_%m_%d_%H%M%S_%f"));
+                champ.save(copy.clone());
+                copy
+            },
+            None => champ_file,
+        };
+        self.hall_of_fame.push((champ_file, champ));
+        if self.hall_of_fame.len() > self.config.hall_of_fame {
+            self.hall_of_fame.remove(0);
+        }
+        let hof_file = self.config.hall_of_fame_file.clone()
+            .unwrap_or_else(|| format!("{}/hall_of_fame.csv", &self.log_dir));
+        let mut writer = csv::Writer::from_path(&hof_file)
+            .unwrap_or_else(|_| panic!("Failed to open {} for writing", &hof_file));
+        writer.write_record(["File"]).expect("Error writing hall of fame");
+        for (file, _) in &self.hall_of_fame {
+            writer.write_record([file]).expect("Error writing hall of fame");
+        }
+        writer.flush().unwrap();
+    }
+
+    /// Build the next generation: elites carry over unchanged, and the rest
+    /// of the slots are filled by the fittest members breeding with selected partners
+    fn breed(&mut self, scores: &[Vec<f64>], fitness: &[f64]) -> Vec<ChessNet> {
+        let ranked = ranking(fitness);
+        let elites = self.config.elites.min(self.num_members);
+        let mut children: Vec<ChessNet> = ranked[..self.num_members - elites].iter().map(|&i| {
+            let j = self.config.selection.select(fitness, &mut thread_rng());
+            self.members[i].merge(&self.members[j], [scores[i][j], scores[j][i]], &self.config)
+        }).collect();
+
+        let mut old: Vec<Option<ChessNet>> = std::mem::take(&mut self.members).into_iter().map(Some).collect();
+        let mut new_members: Vec<ChessNet> = ranked[..elites].iter()
+            .map(|&i| old[i].take().expect("Elites are unique"))
+            .collect();
+        new_members.append(&mut children);
+        new_members
+    }
+
+    /// Points the champion scores against the best from all previous epochs, and the games played
+    fn evaluate(&self, champion_file: &str, champion: &ChessNet, champs_file: &str, ratings: &mut RatingsDb) -> (f64, usize) {
         let mut reader = csv::Reader::from_path(champs_file).unwrap();
         {
             reader.headers().unwrap();
         }
-        let mut wins = 0;
+        let mut score = 0.;
+        let mut games = 0;
         for result in reader.records() {
             let row = result.unwrap();
             let epoch: i32 = row.get(0).unwrap().parse().unwrap();
-            let index: i32 = row.get(1).unwrap().parse().unwrap();
-            let path = format!("{}/{:04}_{:04}.safetensors", &self.log_dir, epoch, index);
-            let opponent = ChessNet::from_file(path.as_str());
-            let result = Arena::play_game(&champion, &opponent);
-            match result {
-                GameResult::WhiteCheckmates => wins += 1,
-                GameResult::BlackCheckmates => (),
-                _ => (),
-            };
-            let result = Arena::play_game(&opponent, &champion);
-            match result {
-                GameResult::WhiteCheckmates => (),
-                GameResult::BlackCheckmates => wins += 1,
-                _ => (),
-            };
+            let index: usize = row.get(1).unwrap().parse().unwrap();
+            let opponent_file = self.member_file(epoch, index);
+            let opponent = ChessNet::from_file(opponent_file.as_str());
+            let white_score = self.play_game(champion, &opponent).white_score();
+            ratings.record(champion_file, &opponent_file, white_score);
+            score += white_score;
+            let white_score = self.play_game(&opponent, champion).white_score();
+            ratings.record(&opponent_file, champion_file, white_score);
+            score += 1. - white_score;
+            games += 2;
         }
-        wins
+        (score, games)
     }
     
-    fn log_champ(&self, writer: &mut csv::Writer<File>, epoch: i32, index: usize, wins: i32) {
-        writer.write_record(&[epoch.to_string(), index.to_string(), wins.to_string()])
+    /// Champion's score rate against each fixed benchmark opponent. Games
+    /// come in pairs sharing an opening, with the champion taking each colour once.
+    fn benchmark(&self, champion_file: &str, champion: &ChessNet, ratings: &mut RatingsDb) -> BTreeMap<String, f64> {
+        let games = self.config.benchmark_games;
+        let mut scores = BTreeMap::new();
+        if games == 0 {
+            return scores;
+        }
+        for (name, opponent) in benchmarks(self.config.benchmark_depth) {
+            let lines = openings::sample(games.div_ceil(2), &mut thread_rng());
+            let mut score = 0.;
+            for game in 0..games {
+                let opening = &lines[game / 2];
+                if game % 2 == 0 {
+                    let white_score = play_from(champion, opponent.as_ref(), opening, &self.config.adjudication, self.config.time_control).outcome.white_score();
+                    ratings.record(champion_file, &name, white_score);
+                    score += white_score;
+                } else {
+                    let white_score = play_from(opponent.as_ref(), champion, opening, &self.config.adjudication, self.config.time_control).outcome.white_score();
+                    ratings.record(&name, champion_file, white_score);
+                    score += 1. - white_score;
+                }
+            }
+            println!("Champion scored {}/{} against {}", score, games, name);
+            scores.insert(name, score / games as f64);
+        }
+        scores
+    }
+
+    fn log_champ(&self, writer: &mut csv::Writer<File>, epoch: i32, index: usize, score: f64) {
+        writer.write_record(&[epoch.to_string(), index.to_string(), score.to_string()])
             .expect("TODO: panic message");
         writer.flush().unwrap();
     }
 }
 
+/// The newest `size` champions still on disk from an earlier run's hall of fame
+fn load_hall_of_fame(file: &str, size: usize) -> Vec<(String, ChessNet)> {
+    let Ok(mut reader) = csv::Reader::from_path(file) else {
+        return vec!();
+    };
+    let files: Vec<String> = reader.records()
+        .filter_map(Result::ok)
+        .filter_map(|row| row.get(0).map(str::to_string))
+        .filter(|champ_file| std::path::Path::new(champ_file).is_file())
+        .collect();
+    files[files.len().saturating_sub(size)..].iter()
+        .map(|champ_file| (champ_file.clone(), ChessNet::from_file(champ_file)))
+        .collect()
+}
+
+/// Result of a finished game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    WhiteWins,
+    Draw,
+    BlackWins,
+}
+
+impl Outcome {
+    /// Points for white: 1 for a win, 0.5 for a draw and 0 for a loss
+    pub fn white_score(&self) -> f64 {
+        match self {
+            Outcome::WhiteWins => 1.,
+            Outcome::Draw => 0.5,
+            Outcome::BlackWins => 0.,
+        }
+    }
+
+    pub fn win_for(color: Color) -> Outcome {
+        match color {
+            Color::White => Outcome::WhiteWins,
+            Color::Black => Outcome::BlackWins,
+        }
+    }
+
+    /// Tie-break for a game that ran out of moves, given white's material lead
+    pub fn from_material(balance: i32) -> Outcome {
+        match balance {
+            b if b > 0 => Outcome::WhiteWins,
+            b if b < 0 => Outcome::BlackWins,
+            _ => Outcome::Draw,
+        }
+    }
+}
+
+impl From<GameResult> for Outcome {
+    fn from(result: GameResult) -> Self {
+        match result {
+            GameResult::WhiteCheckmates | GameResult::BlackResigns => Outcome::WhiteWins,
+            GameResult::BlackCheckmates | GameResult::WhiteResigns => Outcome::BlackWins,
+            GameResult::Stalemate | GameResult::DrawAccepted | GameResult::DrawDeclared => Outcome::Draw,
+        }
+    }
+}
+
+/// Points on the board per colour: a running total and number of games
+#[derive(Clone, Copy, Debug, Default)]
+struct ColourScore {
+    points: f64,
+    games: u32,
+}
+
+impl ColourScore {
+    fn add(&mut self, points: f64) {
+        self.points += points;
+        self.games += 1;
+    }
+
+    fn rate(&self) -> Option<f64> {
+        (self.games > 0).then(|| self.points / self.games as f64)
+    }
+}
+
+/// Results of one epoch's games
+struct Standings {
+    /// `scores[i][j]` is the points member `i` took off member `j`, across both colours
+    scores: Vec<Vec<f64>>,
+    white: Vec<ColourScore>,
+    black: Vec<ColourScore>,
+    /// Round robin white wins, draws and black wins
+    results: [u32; 3],
+}
+
+impl Standings {
+    fn new(num_members: usize) -> Standings {
+        Standings {
+            scores: vec![vec![0.; num_members]; num_members],
+            white: vec![ColourScore::default(); num_members],
+            black: vec![ColourScore::default(); num_members],
+            results: [0; 3],
+        }
+    }
+
+    fn record(&mut self, white: usize, black: usize, outcome: Outcome) {
+        let points = outcome.white_score();
+        self.scores[white][black] += points;
+        self.scores[black][white] += 1. - points;
+        self.white[white].add(points);
+        self.black[black].add(1. - points);
+        self.results[outcome as usize] += 1;
+    }
+
+    /// Mean of each member's white and black score rates, so the first-move
+    /// advantage counts the same for everybody
+    fn fitness(&self) -> Vec<f64> {
+        self.white.iter().zip(&self.black).map(|(white, black)| {
+            match (white.rate(), black.rate()) {
+                (Some(w), Some(b)) => (w + b) / 2.,
+                (Some(rate), None) | (None, Some(rate)) => rate,
+                (None, None) => 0.,
+            }
+        }).collect()
+    }
+
+    fn print_colours(&self) {
+        let total = |colour: &[ColourScore]| colour.iter().fold(ColourScore::default(), |acc, c| ColourScore {
+            points: acc.points + c.points,
+            games: acc.games + c.games,
+        });
+        println!("White scored {:.1}%, black scored {:.1}%",
+                 100. * total(&self.white).rate().unwrap_or(0.),
+                 100. * total(&self.black).rate().unwrap_or(0.));
+        for (i, (white, black)) in self.white.iter().zip(&self.black).enumerate() {
+            println!("  Member {}: {}/{} as white, {}/{} as black",
+                     i, white.points, white.games, black.points, black.games);
+        }
+    }
+}
+
+/// One side's material in pawns, using the usual 1/3/3/5/9 piece values
+pub fn material(board: &Board, color: Color) -> i32 {
+    [(Piece::Pawn, 1), (Piece::Knight, 3), (Piece::Bishop, 3), (Piece::Rook, 5), (Piece::Queen, 9)]
+        .iter()
+        .map(|&(piece, value)| value * (board.pieces(piece) & board.color_combined(color)).popcnt() as i32)
+        .sum()
+}
+
+/// White's material lead in pawns
+pub fn material_balance(board: &Board) -> i32 {
+    material(board, Color::White) - material(board, Color::Black)
+}
+
+/// A finished game: every move from the starting position and how it ended
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub moves: Vec<ChessMove>,
+    pub outcome: Outcome,
+    pub termination: Termination,
+}
+
+/// Play one game between any two players, starting after the `opening` moves
+/// and stopping early as the `adjudication` rules allow. With a time control
+/// the players are clocked from the end of the opening and lose on time.
+pub fn play_from(white: &dyn Player, black: &dyn Player, opening: &[ChessMove], adjudication: &Adjudication,
+                 time_control: Option<TimeControl>) -> GameRecord {
+    let mut game = Game::new();
+    let mut moves = opening.to_vec();
+    for &chess_move in opening {
+        game.make_move(chess_move);
+    }
+    let mut adjudicator = Adjudicator::new(adjudication);
+    let mut clock = time_control.map(Clock::new);
+    let mut draw_offer = None;
+    // take-backs refused on this turn, counted like any other action that isn't a move
+    let mut take_backs = 0;
+    while moves.len() < adjudication.max_plies {
+        let board = game.current_position();
+        let side = board.side_to_move();
+        let player = match side {
+            Color::White => white,
+            Color::Black => black,
+        };
+        let chess_move = match request_move(player, &board, clock.as_mut(), &SearchLimits::default(), &mut draw_offer) {
+            Turn::Move(chess_move) => chess_move,
+            Turn::TakeBack if take_backs < MAX_ACTIONS => {
+                println!("Moves can only be taken back in chessers play");
+                take_backs += 1;
+                continue;
+            },
+            Turn::TakeBack => {
+                println!("{:?} won't move", side);
+                return GameRecord { moves, outcome: Outcome::win_for(!side), termination: Termination::Abandoned };
+            },
+            Turn::Over(outcome, termination) => return GameRecord { moves, outcome, termination },
+        };
+        take_backs = 0;
+        game.make_move(chess_move);
+        moves.push(chess_move);
+        if check_game(&mut game) {
+            let result = game.result().expect("check_game found a result");
+            let termination = match result {
+                GameResult::WhiteCheckmates | GameResult::BlackCheckmates => Termination::Checkmate,
+                GameResult::Stalemate => Termination::Stalemate,
+                _ => Termination::DrawClaim,
+            };
+            return GameRecord { moves, outcome: Outcome::from(result), termination };
+        };
+        if let Some((outcome, termination)) = adjudicator.update(&game.current_position()) {
+            return GameRecord { moves, outcome, termination };
+        }
+    }
+    let (outcome, termination) = adjudicator.finish(&game.current_position());
+    GameRecord { moves, outcome, termination }
+}
+
+/// What came of asking a player to move
+#[derive(Clone, Debug, PartialEq)]
+pub enum Turn {
+    Move(ChessMove),
+    /// The player asked to take back its last move and the reply
+    TakeBack,
+    Over(Outcome, Termination),
+}
+
+/// Ask for a move within `limits`, with the clock running if there is one,
+/// or for how the game ended instead. Resigning, abandoning or making an
+/// illegal move loses; `draw_offer` holds the side with a standing offer,
+/// which lapses once the other side moves. If the flag falls the opponent
+/// wins, or draws when they have nothing left to mate with.
+pub fn request_move(player: &dyn Player, board: &Board, mut clock: Option<&mut Clock>, limits: &SearchLimits,
+                    draw_offer: &mut Option<Color>) -> Turn {
+    let side = board.side_to_move();
+    if *draw_offer == Some(!side) {
+        println!("{:?} offers a draw", !side);
+    }
+    let start = Instant::now();
+    let limits = SearchLimits { time: clock.as_ref().map(|clock| clock.time_left(side)), ..*limits };
+    let mut actions = 0;
+    let chess_move = loop {
+        actions += 1;
+        if actions > MAX_ACTIONS {
+            println!("{:?} won't move", side);
+            return Turn::Over(Outcome::win_for(!side), Termination::Abandoned);
+        }
+        match player.make_limited_move(board, &limits) {
+            Action::Move(chess_move) if board.legal(chess_move) => break chess_move,
+            Action::Move(chess_move) => {
+                println!("{:?} played the illegal move {}", side, chess_move);
+                return Turn::Over(Outcome::win_for(!side), Termination::Abandoned);
+            },
+            Action::Resign => {
+                println!("{:?} resigns", side);
+                return Turn::Over(Outcome::win_for(!side), Termination::Resignation);
+            },
+            Action::AcceptDraw if *draw_offer == Some(!side) => {
+                println!("{:?} accepts the draw", side);
+                return Turn::Over(Outcome::Draw, Termination::DrawAgreed);
+            },
+            Action::AcceptDraw => println!("There is no draw offer to accept"),
+            Action::OfferDraw => *draw_offer = Some(side),
+            Action::TakeBack => {
+                *draw_offer = None;
+                return Turn::TakeBack;
+            },
+            Action::Error(error) => {
+                println!("{:?} can't go on: {}", side, error);
+                return Turn::Over(Outcome::win_for(!side), Termination::Abandoned);
+            },
+        }
+    };
+    if *draw_offer == Some(!side) {
+        *draw_offer = None;
+    }
+    let in_time = clock.as_mut().is_none_or(|clock| clock.punch(side, start.elapsed()));
+    if in_time {
+        Turn::Move(chess_move)
+    } else if can_mate(board, !side) {
+        Turn::Over(Outcome::win_for(!side), Termination::TimeForfeit)
+    } else {
+        Turn::Over(Outcome::Draw, Termination::TimeForfeit)
+    }
+}
+
+/// Where logs, saved models and the ratings database live
+pub fn resources_dir() -> String {
+    env::var("HOME").expect("No HOME dir?") + "/.chessers"
+}
+
 pub fn check_game(game: &mut Game) -> bool{
     if game.can_declare_draw() {
         game.declare_draw();
@@ -209,26 +650,216 @@ mod test {
     use rand::distributions::WeightedIndex;
     use rand::Rng;
     use rand::prelude::*;
-    use crate::arena::Arena;
+    use candle_nn::VarMap;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use chess::{Board, ChessMove, Color, Square};
+    use crate::adjudication::{Adjudication, Termination};
+    use crate::arena::{material_balance, play_from, request_move, Arena, Outcome, Standings, TrainConfig, Turn};
+    use crate::clock::TimeControl;
+    use crate::player::{Action, GreedyPlayer, Player, RandomPlayer};
+    use crate::rating::RatingsDb;
+    use crate::time_manager::SearchLimits;
+    use crate::genetic::selection::Selection;
+    use crate::metrics::{load, METRICS_FILE};
+    use crate::nn::ChessNet;
+
+    /// Settings that keep a test's logs and ratings in its own temporary directory
+    fn temp_config(name: &str) -> TrainConfig {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        TrainConfig { dir: dir.to_str().unwrap().to_string(), ..Default::default() }
+    }
 
     #[test]
     fn run() {
-        let mut arena = Arena::new(2, 2);
+        let config = temp_config("chessers_arena_run");
+        let ratings = format!("{}/ratings.csv", config.dir);
+        let mut arena = Arena::new(2, 2, config);
         arena.train();
+        assert!(std::path::Path::new(&ratings).is_file());
+    }
+
+    #[test]
+    fn run_with_elites_and_hall_of_fame() {
+        let config = TrainConfig {
+            selection: Selection::Tournament { size: 2 },
+            elites: 1,
+            hall_of_fame: 1,
+            ..temp_config("chessers_arena_elites")
+        };
+        let mut arena = Arena::new(3, 2, config);
+        arena.train();
+        assert_eq!(arena.members.len(), 3);
+        assert_eq!(arena.hall_of_fame.len(), 1);
+    }
+
+    #[test]
+    fn hall_of_fame_persists() {
+        let file = std::env::temp_dir().join("chessers_hall_of_fame.csv");
+        let _ = std::fs::remove_file(&file);
+        let config = || TrainConfig {
+            hall_of_fame: 2,
+            hall_of_fame_file: Some(file.to_str().unwrap().to_string()),
+            ..temp_config("chessers_arena_hall_of_fame")
+        };
+        let mut first = Arena::new(2, 1, config());
+        first.train();
+        let champions: Vec<String> = first.hall_of_fame.iter().map(|(champ_file, _)| champ_file.clone()).collect();
+        assert_eq!(champions.len(), 1);
+        let second = Arena::new(2, 1, config());
+        let inherited: Vec<String> = second.hall_of_fame.iter().map(|(champ_file, _)| champ_file.clone()).collect();
+        assert_eq!(inherited, champions);
+    }
+
+    #[test]
+    fn training_games_are_clocked() {
+        // far too little time for a network to move, so every game is lost on time
+        let config = TrainConfig { time_control: Some(TimeControl::from_str("0.000001").unwrap()), ..temp_config("chessers_arena_clock") };
+        let mut arena = Arena::new(2, 1, config);
+        arena.train();
+        let metrics = load(&format!("{}/{}", arena.log_dir, METRICS_FILE));
+        assert_eq!(metrics[0].terminations.keys().collect::<Vec<_>>(), ["time forfeit"]);
+    }
+
+    #[test]
+    fn run_with_sparring_and_benchmarks() {
+        let config = TrainConfig { benchmark_games: 2, benchmark_depth: 1, ..temp_config("chessers_arena_sparring") };
+        let mut arena = Arena::new(2, 1, config);
+        arena.sparring = vec![("greedy".to_string(), Box::new(GreedyPlayer))];
+
+        // a game with each colour against the partner, on top of the round robin
+        let mut standings = Standings::new(2);
+        let mut ratings = RatingsDb::open(format!("{}/sparring.csv", arena.config.dir));
+        arena.play_outsiders(0, 1, &arena.members[1], &mut standings, &mut ratings);
+        assert_eq!((standings.white[1].games, standings.black[1].games), (1, 1));
+        assert_eq!(standings.white[0].games + standings.black[0].games, 0);
+
+        arena.train();
+        let metrics = load(&format!("{}/{}", arena.log_dir, METRICS_FILE));
+        assert_eq!(metrics[0].benchmarks.keys().collect::<Vec<_>>(), ["greedy", "material", "minimax:1", "random"]);
+        let ratings = RatingsDb::open(format!("{}/ratings.csv", arena.config.dir));
+        assert!(ratings.ratings.keys().any(|name| name.ends_with("0000_0001.safetensors")));
+    }
+
+    #[test]
+    fn draws_count_half() {
+        let mut standings = Standings::new(2);
+        standings.record(0, 1, Outcome::Draw);
+        standings.record(1, 0, Outcome::WhiteWins);
+        assert_eq!(standings.scores, vec![vec![0., 0.5], vec![1.5, 0.]]);
+        assert_eq!(standings.fitness(), vec![0.25, 0.75]);
+        assert_eq!(standings.results, [1, 1, 0]);
+    }
+
+    #[test]
+    fn material_tie_break() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(material_balance(&board), 5);
+        assert_eq!(Outcome::from_material(material_balance(&board)), Outcome::WhiteWins);
+        assert_eq!(Outcome::from_material(material_balance(&Board::default())), Outcome::Draw);
+    }
+
+    struct SlowPlayer;
+
+    impl Player for SlowPlayer {
+        fn make_move(&self, board: &Board) -> Action {
+            std::thread::sleep(Duration::from_millis(20));
+            RandomPlayer::default().make_move(board)
+        }
+    }
+
+    /// Tries to accept a draw whether or not one was offered
+    struct Peacemaker;
+
+    impl Player for Peacemaker {
+        fn make_move(&self, _board: &Board) -> Action {
+            Action::AcceptDraw
+        }
+    }
+
+    /// Does the same thing every turn
+    struct Quitter(Action);
+
+    impl Player for Quitter {
+        fn make_move(&self, _board: &Board) -> Action {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn resignation_and_abandonment() {
+        let rules = Adjudication::default();
+        let game = play_from(&Quitter(Action::Resign), &RandomPlayer::default(), &[], &rules, None);
+        assert_eq!((game.outcome, game.termination), (Outcome::BlackWins, Termination::Resignation));
+        let game = play_from(&RandomPlayer::default(), &Quitter(Action::Error("input closed".to_string())), &[], &rules, None);
+        assert_eq!((game.outcome, game.termination), (Outcome::WhiteWins, Termination::Abandoned));
+        let illegal = ChessMove::new(Square::E2, Square::E5, None);
+        let game = play_from(&Quitter(Action::Move(illegal)), &RandomPlayer::default(), &[], &rules, None);
+        assert_eq!(game.termination, Termination::Abandoned);
+        // automated games refuse take-backs, and asking too often forfeits
+        let game = play_from(&RandomPlayer::default(), &Quitter(Action::TakeBack), &[], &rules, None);
+        assert_eq!((game.outcome, game.termination), (Outcome::WhiteWins, Termination::Abandoned));
+        assert_eq!(game.moves.len(), 1);
+    }
+
+    #[test]
+    fn draw_offers() {
+        let rules = Adjudication::default();
+        // an accept without an offer is refused, and asking too often forfeits
+        let game = play_from(&Peacemaker, &RandomPlayer::default(), &[], &rules, None);
+        assert_eq!((game.outcome, game.termination), (Outcome::BlackWins, Termination::Abandoned));
+
+        let mut offer = None;
+        let board = Board::default();
+        let offering = Quitter(Action::OfferDraw);
+        assert!(matches!(request_move(&offering, &board, None, &SearchLimits::default(), &mut offer), Turn::Over(..)));
+        assert_eq!(offer, Some(Color::White));
+        let reply = board.make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+        let result = request_move(&Peacemaker, &reply, None, &SearchLimits::default(), &mut offer);
+        assert_eq!(result, Turn::Over(Outcome::Draw, Termination::DrawAgreed));
+        // moving instead of accepting lets the offer lapse
+        let mut offer = Some(Color::White);
+        assert!(matches!(request_move(&RandomPlayer::default(), &reply, None, &SearchLimits::default(), &mut offer), Turn::Move(_)));
+        assert_eq!(offer, None);
+    }
+
+    #[test]
+    fn flag_falls() {
+        let control = TimeControl::from_str("0.01").unwrap();
+        let game = play_from(&SlowPlayer, &RandomPlayer::default(), &[], &Adjudication::default(), Some(control));
+        assert_eq!(game.termination, Termination::TimeForfeit);
+        assert_eq!(game.outcome, Outcome::BlackWins);
+        assert!(game.moves.is_empty());
+    }
+
+    #[test]
+    fn merge_keeps_every_tensor() {
+        let a = ChessNet::new(VarMap::new());
+        let b = ChessNet::new(VarMap::new());
+        let child = a.merge(&b, [1., 1.], &TrainConfig::default());
+        let parent_shapes: Vec<(String, Vec<usize>)> = a.named_tensors().into_iter()
+            .map(|(name, t)| (name, t.dims().to_vec()))
+            .collect();
+        let child_shapes: Vec<(String, Vec<usize>)> = child.named_tensors().into_iter()
+            .map(|(name, t)| (name, t.dims().to_vec()))
+            .collect();
+        assert_eq!(parent_shapes, child_shapes);
+        assert_eq!(child.step_sizes.len(), parent_shapes.len());
     }
 
     #[test]
     fn masking() {
-        let a = Tensor::new(vec![3f32; 2], &Device::Cpu);
+        let _a = Tensor::new(vec![3f32; 2], &Device::Cpu);
         let t = Tensor::ones((6, 2), DType::F64, &Device::Cpu).unwrap();
         let dist = WeightedIndex::new([2, 1]).unwrap();
         let mut indices: Vec<Vec<f64>> = t.zeros_like().unwrap().to_vec2().unwrap();
-        for i in 0..t.dim(0).unwrap() {
-            for j in 0..t.dim(1).unwrap() {
-                indices[i][j] = thread_rng().sample(&dist) as f64;
+        for row in indices.iter_mut() {
+            for index in row.iter_mut() {
+                *index = thread_rng().sample(&dist) as f64;
             }
         }
-        let indices = Tensor::new(indices, &Device::Cpu).unwrap();
+        let _indices = Tensor::new(indices, &Device::Cpu).unwrap();
         let indices = t.rand_like(0., 1.).unwrap();
         println!("{:?}", indices.to_string());
     }
@@ -0,0 +1,15 @@
+use crate::player::{GreedyPlayer, MaterialPlayer, MinimaxPlayer, Player, RandomPlayer};
+
+/// Depth the benchmark minimax player searches to unless told otherwise
+pub const BENCHMARK_DEPTH: u32 = 2;
+
+/// The fixed opponents every champion is measured against, by name
+pub fn benchmarks(depth: u32) -> Vec<(String, Box<dyn Player>)> {
+    vec![
+        ("random".to_string(), Box::new(RandomPlayer::default())),
+        ("greedy".to_string(), Box::new(GreedyPlayer)),
+        ("material".to_string(), Box::new(MaterialPlayer)),
+        (format!("minimax:{}", depth), Box::new(MinimaxPlayer { depth })),
+    ]
+}
+
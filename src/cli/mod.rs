@@ -1,9 +1,31 @@
-use chess::Game;
-use clap::{Args, Parser, Subcommand};
-use crate::arena::{Arena, check_game};
+use std::rc::Rc;
+use std::time::Duration;
+use std::str::FromStr;
+use chess::{Board, ChessMove, Color, Game};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap::error::ErrorKind;
+use crate::adjudication::{Adjudication, Termination};
+use crate::arena::{resources_dir, request_move, Arena, Outcome, TrainConfig, Turn, check_game};
+use crate::baseline::BENCHMARK_DEPTH;
+use crate::clock::{Clock, TimeControl};
+use crate::time_manager::SearchLimits;
+use crate::gauntlet::{find_opponents, Gauntlet};
+use crate::genetic::crossover::{Blend, Crossover, PerFilter, SinglePoint, Uniform};
+use crate::genetic::mutation::Mutation;
+use crate::inspect::Inspection;
+use crate::metrics;
+use crate::genetic::selection::Selection;
 use crate::nn::ChessNet;
-use crate::player::{Player, HumanPlayer, RandomPlayer};
-use crate::ui::{UI, ConsoleUI};
+use crate::progress::Progress;
+use crate::player::{Player, HumanPlayer, ResigningPlayer};
+use crate::rating::{player_id, RatingsDb};
+use crate::spec::{rating_id, PlayerSpec};
+use crate::sprt::{Decision, Sprt};
+use crate::tournament::{Format, Tournament};
+use crate::tui::{TerminalPlayer, TerminalUI};
+use crate::ui::{UI, ConsoleUI, Style};
+use crate::web::serve;
+use crate::weights;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -20,17 +42,303 @@ enum Commands {
 
     /// Run a genetic optimization to train a ChessBot
     Train (TrainArgs),
+
+    /// List the rating of every player that has played a game
+    Ratings (RatingsArgs),
+
+    /// Play one model against a pool of opponents
+    Gauntlet (GauntletArgs),
+
+    /// Play two models until a sequential probability ratio test decides which is stronger
+    Sprt (SprtArgs),
+
+    /// Run a round robin or Swiss tournament between any players
+    Tournament (TournamentArgs),
+
+    /// Serve a browser board, saved games and training charts on localhost
+    Serve (ServeArgs),
+
+    /// Show a model's output for a position as heatmaps, with its ranked moves
+    Inspect (InspectArgs),
+
+    /// Weight statistics for saved models
+    Model (ModelArgs),
+
+    /// Summarise a training run's metrics with a table and plots
+    Report (ReportArgs),
+
+    /// Play a run's epoch champions against each other and fit a rating to each
+    Progress (ProgressArgs),
+}
+
+#[derive(Args)]
+struct ProgressArgs {
+    /// Run directory containing champs.csv
+    run_dir: String,
+
+    /// Games between each pair of champions, alternating colours
+    #[arg(short, long, default_value_t = 2)]
+    games: usize,
+
+    /// Only play each champion against this many others picked at random
+    #[arg(short, long)]
+    sample: Option<usize>,
+
+    /// Also draw the rating curve to this SVG file
+    #[arg(long)]
+    svg: Option<String>,
+
+    #[command(flatten)]
+    rules: MatchArgs,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    /// Run directory, or the name of a run under ~/.chessers/logs
+    run: String,
+
+    /// Also draw every curve to this SVG file
+    #[arg(long)]
+    svg: Option<String>,
+}
+
+#[derive(Args)]
+struct ModelArgs {
+    #[command(subcommand)]
+    command: ModelCommand,
+}
+
+#[derive(Subcommand)]
+enum ModelCommand {
+    /// Architecture, parameter count and per-tensor statistics
+    Info {
+        file: String,
+    },
+
+    /// How far each tensor moved from one model to another
+    Diff {
+        a: String,
+        b: String,
+    },
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    /// Safetensors file to inspect
+    #[arg(short, long)]
+    model: String,
+
+    /// Position to inspect, the starting position by default
+    #[arg(short, long)]
+    fen: Option<String>,
+
+    /// Number of ranked moves to list
+    #[arg(short, long, default_value_t = 10)]
+    top: usize,
+
+    /// Also draw both heatmaps to this SVG file
+    #[arg(long)]
+    svg: Option<String>,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
 }
 
 #[derive(Args)]
 struct PlayArgs {
-    /// Set white player. Pass a safetensors file to use a ChessBot
-    #[arg(short, long, default_value = "human")]
+    /// Set white player: human, a safetensors file, random[:seed=N], greedy, material, minimax[:depth=N] or net:FILE[:temp=T]
+    #[arg(short, long, default_value = "human", value_parser = player_spec)]
     white: Option<String>,
 
-    /// Set black player. Pass a safetensors file to use a ChessBot
-    #[arg(short, long, default_value = "human")]
-    black: Option<String>
+    /// Set black player: human, a safetensors file, random[:seed=N], greedy, material, minimax[:depth=N] or net:FILE[:temp=T]
+    #[arg(short, long, default_value = "human", value_parser = player_spec)]
+    black: Option<String>,
+
+    /// Clock both players, e.g. 300+2 for five minutes plus two seconds a move, or 40/5400
+    #[arg(short, long)]
+    time_control: Option<TimeControl>,
+
+    /// Seconds a thinking bot spends on every move, overriding the clock
+    #[arg(long)]
+    movetime: Option<f64>,
+
+    /// Depth a thinking bot searches to
+    #[arg(long)]
+    depth: Option<u32>,
+
+    /// Positions a thinking bot searches per move
+    #[arg(long)]
+    nodes: Option<u64>,
+
+    /// Bots resign once this many pawns of material behind
+    #[arg(long)]
+    resign: Option<i32>,
+
+    /// Model that suggests a move when a human types hint
+    #[arg(long)]
+    hint: Option<String>,
+
+    /// How to draw the board
+    #[arg(short, long, value_enum, default_value_t = StyleKind::Plain)]
+    style: StyleKind,
+
+    /// Play full screen, showing what the hint model, or else the first bot, thinks of each position
+    #[arg(long)]
+    tui: bool,
+}
+
+impl PlayArgs {
+    /// Humans get the hint model and can turn the board
+    fn player(&self, source: &str, ui: &Rc<ConsoleUI>) -> Box<dyn Player> {
+        match source {
+            "human" => Box::new(HumanPlayer {
+                hint: self.hint.as_deref().map(ChessNet::from_file),
+                ui: ui.clone(),
+            }),
+            _ => self.bot(source),
+        }
+    }
+
+    fn terminal_player(&self, source: &str, ui: &Rc<TerminalUI>) -> Box<dyn Player> {
+        match source {
+            "human" => Box::new(TerminalPlayer { ui: ui.clone() }),
+            _ => self.bot(source),
+        }
+    }
+
+    /// Bots may resign
+    fn bot(&self, source: &str) -> Box<dyn Player> {
+        let player = create_player(source);
+        match self.resign {
+            Some(margin) => Box::new(ResigningPlayer { player, margin }),
+            None => player,
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        SearchLimits {
+            time: None,
+            movetime: self.movetime.map(Duration::from_secs_f64),
+            depth: self.depth,
+            nodes: self.nodes,
+        }
+    }
+}
+
+#[derive(Args)]
+struct RatingsArgs {
+    /// Only show the highest rated players
+    #[arg(short, long)]
+    top: Option<usize>,
+}
+
+#[derive(Args)]
+struct GauntletArgs {
+    /// Safetensors file of the model under test
+    #[arg(short, long)]
+    model: String,
+
+    /// Directory of safetensors files, or a glob matching them
+    #[arg(short, long)]
+    opponents: String,
+
+    /// Games against each opponent, alternating colours
+    #[arg(short, long, default_value_t = 2)]
+    games: usize,
+
+    #[command(flatten)]
+    rules: MatchArgs,
+}
+
+#[derive(Args)]
+struct SprtArgs {
+    /// Safetensors file of the candidate model
+    #[arg(long)]
+    a: String,
+
+    /// Safetensors file of the model to beat
+    #[arg(long)]
+    b: String,
+
+    /// Elo gain of A over B under the null hypothesis
+    #[arg(long, default_value_t = 0.)]
+    elo0: f64,
+
+    /// Elo gain of A over B under the alternative hypothesis, above elo0
+    #[arg(long, default_value_t = 10.)]
+    elo1: f64,
+
+    /// Chance of accepting the alternative when the null is true, between 0 and 1
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    /// Chance of accepting the null when the alternative is true, between 0 and 1
+    #[arg(long, default_value_t = 0.05)]
+    beta: f64,
+
+    /// Give up without a decision after this many games
+    #[arg(long, default_value_t = 20000)]
+    max_games: usize,
+
+    #[command(flatten)]
+    rules: MatchArgs,
+}
+
+#[derive(Args)]
+struct TournamentArgs {
+    /// Players: safetensors files, or specs such as random:seed=42, greedy, material, minimax:3, net:FILE:temp=0.5 or human
+    #[arg(required = true, num_args = 2.., value_parser = player_spec)]
+    players: Vec<String>,
+
+    /// Pairing system
+    #[arg(short, long, value_enum, default_value_t = FormatKind::RoundRobin)]
+    format: FormatKind,
+
+    /// Games between each pair in a round robin, alternating colours
+    #[arg(short, long, default_value_t = 2)]
+    games: usize,
+
+    /// Number of Swiss rounds
+    #[arg(short, long, default_value_t = 5)]
+    rounds: usize,
+
+    /// Directory for the crosstable and PGN, defaults to ~/.chessers/tournaments/<time>
+    #[arg(short, long)]
+    out: Option<String>,
+
+    #[command(flatten)]
+    rules: MatchArgs,
+}
+
+#[derive(Clone, ValueEnum)]
+enum StyleKind {
+    /// Letters and dots
+    Plain,
+    /// Chess glyphs with coordinates
+    Unicode,
+    /// Glyphs on coloured squares with the last move and checks highlighted
+    Colour,
+}
+
+impl StyleKind {
+    fn style(&self) -> Style {
+        match self {
+            StyleKind::Plain => Style::Plain,
+            StyleKind::Unicode => Style::Unicode,
+            StyleKind::Colour => Style::Colour,
+        }
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum FormatKind {
+    /// Everybody plays everybody
+    RoundRobin,
+    /// Players on similar scores meet each round
+    Swiss,
 }
 
 #[derive(Args)]
@@ -42,52 +350,389 @@ struct TrainArgs {
     /// Number of generations to run (increases runtime by n)
     #[arg(short, long, default_value_t = 2)]
     generations: i32,
+
+    /// How each child inherits weights from its two parents
+    #[arg(short, long, value_enum, default_value_t = CrossoverKind::Uniform)]
+    crossover: CrossoverKind,
+
+    /// Mutation operator applied to each child after crossover
+    #[arg(short, long, value_enum, default_value_t = MutationKind::Scale)]
+    mutation: MutationKind,
+
+    /// Chance that any single weight is mutated
+    #[arg(long, default_value_t = 0.2)]
+    mutation_rate: f64,
+
+    /// Standard deviation of the noise used by gaussian, reset and adaptive mutation
+    #[arg(long, default_value_t = 0.1)]
+    sigma: f64,
+
+    /// How breeding partners are picked
+    #[arg(short, long, value_enum, default_value_t = SelectionKind::Roulette)]
+    selection: SelectionKind,
+
+    /// Number of members drawn for each tournament selection
+    #[arg(long, default_value_t = 3)]
+    tournament_size: usize,
+
+    /// Number of top members carried into the next generation unchanged
+    #[arg(short, long, default_value_t = 0)]
+    elites: usize,
+
+    /// Number of past champions every member must also beat, 0 to disable
+    #[arg(long, default_value_t = 0)]
+    hall_of_fame: usize,
+
+    /// File the hall of fame is kept in between runs, ~/.chessers/hall_of_fame.csv by default
+    #[arg(long)]
+    hall_of_fame_file: Option<String>,
+
+    /// Fixed opponent every member also plays with both colours, e.g. greedy or minimax:2. Repeat for more
+    #[arg(long, value_parser = player_spec)]
+    sparring: Vec<String>,
+
+    /// Games each champion plays against the random, greedy, material and minimax benchmarks, 0 to skip them
+    #[arg(long, default_value_t = 2)]
+    benchmark_games: usize,
+
+    /// Search depth of the minimax benchmark
+    #[arg(long, default_value_t = BENCHMARK_DEPTH)]
+    benchmark_depth: u32,
+
+    #[command(flatten)]
+    rules: MatchArgs,
+}
+
+/// Rules for automated games. Adjudication judges positions on material in pawns.
+#[derive(Args)]
+struct MatchArgs {
+    /// Clock both players, e.g. 300+2 for five minutes plus two seconds a move, or 40/5400
+    #[arg(short, long)]
+    time_control: Option<TimeControl>,
+
+    /// Longest game in half moves, after which it is drawn
+    #[arg(long, default_value_t = 2000)]
+    max_plies: usize,
+
+    /// Award games that hit the move limit to whoever is ahead on material instead of drawing them
+    #[arg(long)]
+    adjudicate_material: bool,
+
+    /// Material lead that wins the game once held for --win-plies half moves
+    #[arg(long)]
+    win_margin: Option<i32>,
+
+    /// Half moves a winning lead must be held
+    #[arg(long, default_value_t = 10)]
+    win_plies: usize,
+
+    /// Draw once neither side has more than this much material besides the king
+    #[arg(long)]
+    draw_material: Option<i32>,
+
+    /// Draw once the material balance stays within this margin for --draw-plies half moves
+    #[arg(long)]
+    draw_margin: Option<i32>,
+
+    /// Half moves the balance must stay level
+    #[arg(long, default_value_t = 100)]
+    draw_plies: usize,
+}
+
+impl MatchArgs {
+    fn adjudication(&self) -> Adjudication {
+        Adjudication {
+            max_plies: self.max_plies,
+            material_tie_break: self.adjudicate_material,
+            win_margin: self.win_margin,
+            win_plies: self.win_plies,
+            draw_material: self.draw_material,
+            draw_margin: self.draw_margin,
+            draw_plies: self.draw_plies,
+        }
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum SelectionKind {
+    /// Chance proportional to fitness
+    Roulette,
+    /// Fittest of a random sample
+    Tournament,
+    /// Chance proportional to rank
+    Rank,
+}
+
+#[derive(Clone, ValueEnum)]
+enum CrossoverKind {
+    /// Pick every weight from either parent
+    Uniform,
+    /// Pick whole convolution filters from either parent
+    Filter,
+    /// Weighted average of both parents
+    Blend,
+    /// Take the early layers from one parent and the rest from the other
+    SinglePoint,
+}
+
+#[derive(Clone, ValueEnum)]
+enum MutationKind {
+    /// Multiply by 0.5 or 2
+    Scale,
+    /// Add gaussian noise
+    Gaussian,
+    /// Replace with a random weight
+    Reset,
+    /// Negate
+    SignFlip,
+    /// Gaussian noise with self-adapting per-layer step sizes
+    Adaptive,
+}
+
+impl TrainArgs {
+    fn config(&self) -> TrainConfig {
+        let rate = self.mutation_rate;
+        let sigma = self.sigma;
+        let mutation = match self.mutation {
+            MutationKind::Scale => Mutation::Scale { rate },
+            MutationKind::Gaussian => Mutation::Gaussian { rate, sigma },
+            MutationKind::Reset => Mutation::Reset { rate, sigma },
+            MutationKind::SignFlip => Mutation::SignFlip { rate },
+            MutationKind::Adaptive => Mutation::Adaptive { rate, sigma },
+        };
+        let crossover: Box<dyn Crossover> = match self.crossover {
+            CrossoverKind::Uniform => Box::new(Uniform),
+            CrossoverKind::Filter => Box::new(PerFilter),
+            CrossoverKind::Blend => Box::new(Blend),
+            CrossoverKind::SinglePoint => Box::new(SinglePoint),
+        };
+        let selection = match self.selection {
+            SelectionKind::Roulette => Selection::Roulette,
+            SelectionKind::Tournament => Selection::Tournament { size: self.tournament_size },
+            SelectionKind::Rank => Selection::Rank,
+        };
+        TrainConfig {
+            crossover,
+            mutation,
+            selection,
+            elites: self.elites,
+            hall_of_fame: self.hall_of_fame,
+            hall_of_fame_file: Some(self.hall_of_fame_file.clone()
+                .unwrap_or_else(|| format!("{}/hall_of_fame.csv", resources_dir()))),
+            adjudication: self.rules.adjudication(),
+            time_control: self.rules.time_control,
+            benchmark_games: self.benchmark_games,
+            benchmark_depth: self.benchmark_depth,
+            dir: resources_dir(),
+        }
+    }
 }
 
 impl Cli {
     pub fn run(&self) {
         match &self.command {
             Commands::Play(args) => {
-                let white = create_player(args.white.as_ref().expect("How did white get unset?").as_str());
-                let black = create_player(args.black.as_ref().expect("How did black get unset?").as_str());
-                play_game(white, black);
+                let white_source = args.white.as_ref().expect("How did white get unset?").as_str();
+                let black_source = args.black.as_ref().expect("How did black get unset?").as_str();
+                // face a lone human playing Black from their own side
+                let flipped = black_source == "human" && white_source != "human";
+                let outcome = if args.tui {
+                    let engine = args.hint.clone()
+                        .or([white_source, black_source].into_iter().find_map(|source| match source.parse() {
+                            Ok(PlayerSpec::Net { path, .. }) => Some(path),
+                            _ => None,
+                        }))
+                        .map(|path| ChessNet::from_file(&path));
+                    let gui = Rc::new(TerminalUI::new(engine, flipped));
+                    let white = args.terminal_player(white_source, &gui);
+                    let black = args.terminal_player(black_source, &gui);
+                    play_game(white, black, gui.as_ref(), args.time_control, &args.limits())
+                } else {
+                    let gui = Rc::new(ConsoleUI::new(args.style.style(), flipped));
+                    let white = args.player(white_source, &gui);
+                    let black = args.player(black_source, &gui);
+                    play_game(white, black, gui.as_ref(), args.time_control, &args.limits())
+                };
+                if let Some(outcome) = outcome {
+                    let mut ratings = RatingsDb::open_default();
+                    ratings.record(&rating_id(white_source), &rating_id(black_source), outcome.white_score());
+                    ratings.commit();
+                }
             },
             Commands::Train(args) => {
-                let mut arena = Arena::new(args.population, args.generations);
+                let mut arena = Arena::new(args.population, args.generations, args.config());
+                arena.sparring = args.sparring.iter().map(|spec| (spec.clone(), create_player(spec))).collect();
                 arena.train();
             },
+            Commands::Gauntlet(args) => {
+                let model = player_id(&args.model);
+                let opponents: Vec<String> = find_opponents(&args.opponents).into_iter()
+                    .filter(|file| player_id(file) != model)
+                    .collect();
+                let mut ratings = RatingsDb::open_default();
+                Gauntlet::run(&args.model, &opponents, args.games, &args.rules.adjudication(), args.rules.time_control, &mut ratings).print();
+            },
+            Commands::Sprt(args) => {
+                let sprt = Sprt::new(args.elo0, args.elo1, args.alpha, args.beta)
+                    .unwrap_or_else(|error| Cli::command().error(ErrorKind::ValueValidation, error).exit());
+                let mut ratings = RatingsDb::open_default();
+                let (decision, record) = sprt.run(&args.a, &args.b, args.max_games, &args.rules.adjudication(), args.rules.time_control, &mut ratings);
+                match decision {
+                    Decision::AcceptH1 => println!("H1 accepted: A is at least {} Elo stronger than B", args.elo1),
+                    Decision::AcceptH0 => println!("H0 accepted: A is no more than {} Elo stronger than B", args.elo0),
+                    Decision::Continue => println!("No decision after {} games", record.games()),
+                }
+                println!("Elo difference: {:+.1} ± {:.1}", record.elo(), record.elo_error());
+            },
+            Commands::Tournament(args) => {
+                let entrants = args.players.iter()
+                    .map(|spec| (player_id(spec), create_player(spec)))
+                    .collect();
+                let format = match args.format {
+                    FormatKind::RoundRobin => Format::RoundRobin { games: args.games },
+                    FormatKind::Swiss => Format::Swiss { rounds: args.rounds },
+                };
+                let mut tournament = Tournament::new(entrants);
+                tournament.time_control = args.rules.time_control;
+                let mut ratings = RatingsDb::open_default();
+                tournament.run(format, &args.rules.adjudication(), &mut ratings);
+                tournament.print_standings();
+                let out = args.out.clone().unwrap_or_else(|| format!("{}/tournaments/{}",
+                    resources_dir(), chrono::Utc::now().format("%Y_%m_%d_%H%M%S")));
+                tournament.save(&out);
+                println!("Crosstable and games saved to {}", out);
+            },
+            Commands::Serve(args) => serve(args.port),
+            Commands::Model(args) => match &args.command {
+                ModelCommand::Info { file } => weights::print_info(&weights::info(&ChessNet::from_file(file))),
+                ModelCommand::Diff { a, b } => {
+                    let (diffs, unmatched) = weights::diff(&ChessNet::from_file(a), &ChessNet::from_file(b));
+                    weights::print_diff(&diffs, &unmatched);
+                },
+            },
+            Commands::Report(args) => {
+                let dir = if std::path::Path::new(&args.run).is_dir() {
+                    args.run.clone()
+                } else {
+                    format!("{}/logs/{}", resources_dir(), args.run)
+                };
+                let metrics = metrics::load(&format!("{}/{}", dir, metrics::METRICS_FILE));
+                print!("{}", metrics::report(&metrics));
+                if let Some(file) = &args.svg {
+                    std::fs::write(file, metrics::svg(&metrics::curves(&metrics))).unwrap_or_else(|e| panic!("Couldn't write {}: {}", file, e));
+                    println!("Curves drawn to {}", file);
+                }
+            },
+            Commands::Progress(args) => {
+                let champions = Progress::champions(&args.run_dir);
+                let mut ratings = RatingsDb::open_default();
+                let progress = Progress::run(champions, args.games, args.sample, &args.rules.adjudication(), args.rules.time_control, &mut ratings);
+                progress.print();
+                if let Some(file) = &args.svg {
+                    std::fs::write(file, metrics::svg(&[progress.curve()])).unwrap_or_else(|e| panic!("Couldn't write {}: {}", file, e));
+                    println!("Rating curve drawn to {}", file);
+                }
+            },
+            Commands::Inspect(args) => {
+                let board = match &args.fen {
+                    Some(fen) => Board::from_str(fen).unwrap_or_else(|e| panic!("Invalid FEN {}: {}", fen, e)),
+                    None => Board::default(),
+                };
+                let inspection = Inspection::new(&ChessNet::from_file(&args.model), board);
+                print!("{}", inspection.render(args.top));
+                if let Some(file) = &args.svg {
+                    std::fs::write(file, inspection.svg()).unwrap_or_else(|e| panic!("Couldn't write {}: {}", file, e));
+                    println!("Heatmaps drawn to {}", file);
+                }
+            },
+            Commands::Ratings(args) => {
+                let ratings = RatingsDb::open_default();
+                println!("{:>4}  {:>6}  {:>5}  {:>13}  {:>5}  Player", "Rank", "Rating", "RD", "95% interval", "Games");
+                for (rank, (player, rating)) in ratings.ranked().iter().take(args.top.unwrap_or(usize::MAX)).enumerate() {
+                    let (low, high) = rating.interval();
+                    println!("{:>4}  {:>6.0}  {:>5.0}  {:>6.0}-{:<6.0}  {:>5}  {}",
+                             rank + 1, rating.rating, rating.deviation, low, high, rating.games, player);
+                }
+            },
         }
     }
 }
 
 
+/// Build a player from a spec such as `random:seed=42` or `minimax:3`, see [`PlayerSpec`]
 fn create_player(source: &str) -> Box<dyn Player> {
-    match source { 
-            "human" => Box::new(HumanPlayer {}),
-            file if file.contains("safetensors") => Box::new(ChessNet::from_file(file)),
-            _ => Box::new(RandomPlayer {})
-        }
+    source.parse::<PlayerSpec>()
+        .and_then(|spec| spec.build())
+        .unwrap_or_else(|e| panic!("{}", e))
 }
 
+/// Check a player spec while parsing arguments, so a bad one is reported up front
+fn player_spec(source: &str) -> Result<String, String> {
+    source.parse::<PlayerSpec>().map(|_| source.to_string())
+}
 
-fn play_game(p1: Box<dyn Player>, p2: Box<dyn Player>) {
+
+/// Play a game on the console. Returns None if it was abandoned, so it goes unrated.
+fn play_game(white: Box<dyn Player>, black: Box<dyn Player>, gui: &dyn UI, time_control: Option<TimeControl>,
+             limits: &SearchLimits) -> Option<Outcome> {
     let mut game = Game::new();
-    let gui = ConsoleUI {};
-    gui.update(&game.current_position());
-
-    for _ in 1..1000 {
-        let p1_move = p1.make_move(&game.current_position());
-        game.make_move(p1_move);
-        if check_game(&mut game) {
-            gui.update(&game.current_position());
-            break;
+    let mut moves: Vec<ChessMove> = Vec::new();
+    gui.update(&game.current_position(), &moves);
+    let mut clock = time_control.map(Clock::new);
+    // the clock as it stood before each move, so take-backs give the time back too
+    let mut clocks: Vec<Option<Clock>> = Vec::new();
+    let mut draw_offer = None;
+
+    for _ in 0..2000 {
+        let board = game.current_position();
+        let player = match board.side_to_move() {
+            Color::White => &white,
+            Color::Black => &black,
         };
-        let p2_move = p2.make_move(&game.current_position());
-        game.make_move(p2_move);
-        if check_game(&mut game) {
-            gui.update(&game.current_position());
-            break;
+        let before = clock.clone();
+        let chess_move = match request_move(player.as_ref(), &board, clock.as_mut(), limits, &mut draw_offer) {
+            Turn::Move(chess_move) => {
+                clocks.push(before);
+                chess_move
+            },
+            Turn::TakeBack if moves.len() < 2 => {
+                gui.message("There are no moves to take back");
+                continue;
+            },
+            Turn::TakeBack => {
+                moves.truncate(moves.len() - 2);
+                clock = clocks[clocks.len() - 2].clone();
+                clocks.truncate(clocks.len() - 2);
+                game = Game::new();
+                for &chess_move in &moves {
+                    game.make_move(chess_move);
+                }
+                gui.update(&game.current_position(), &moves);
+                if let Some(clock) = &clock {
+                    gui.show_clock(clock);
+                }
+                continue;
+            },
+            Turn::Over(_, Termination::Abandoned) => return None,
+            Turn::Over(outcome, termination) => {
+                let result = format!("{:?} by {}", outcome, termination);
+                gui.message(&result);
+                gui.game_over(&result);
+                return Some(outcome);
+            },
         };
-        gui.update(&game.current_position());
+        game.make_move(chess_move);
+        moves.push(chess_move);
+        let over = check_game(&mut game);
+        gui.update(&game.current_position(), &moves);
+        if let Some(clock) = &clock {
+            gui.show_clock(clock);
+        }
+        if over {
+            break;
+        }
     }
+    let result = game.result();
+    gui.game_over(&result.map_or("Move limit reached".to_string(), |result| format!("{:?}", result)));
+    Some(result.map_or(Outcome::Draw, Outcome::from))
 }
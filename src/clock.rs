@@ -0,0 +1,210 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use chess::{BitBoard, Board, Color, Piece};
+
+/// How much thinking time each player gets, written like `300+2` (five
+/// minutes plus two seconds a move), `40/5400` (90 minutes for every 40
+/// moves) or `40/5400+30`. Times are in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+    /// Moves per session, after which `base` is added again
+    pub moves: Option<u32>,
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let seconds = |text: &str| text.trim().parse::<f64>()
+            .ok()
+            .filter(|v| *v >= 0. && v.is_finite())
+            .map(Duration::from_secs_f64)
+            .ok_or_else(|| format!("Invalid time {:?} in time control {:?}", text, s));
+        let (moves, rest) = match s.split_once('/') {
+            Some((moves, rest)) => {
+                let moves = moves.trim().parse::<u32>().ok().filter(|&m| m > 0)
+                    .ok_or_else(|| format!("Invalid move count {:?} in time control {:?}", moves, s))?;
+                (Some(moves), rest)
+            },
+            None => (None, s),
+        };
+        let (base, increment) = match rest.split_once('+') {
+            Some((base, increment)) => (seconds(base)?, seconds(increment)?),
+            None => (seconds(rest)?, Duration::ZERO),
+        };
+        Ok(TimeControl { base, increment, moves })
+    }
+}
+
+/// Clock state handed to the player about to move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeLeft {
+    pub remaining: Duration,
+    pub opponent: Duration,
+    pub increment: Duration,
+    /// Moves until the next session's time is added, if the control has sessions
+    pub moves_to_go: Option<u32>,
+}
+
+/// Chess clock for both players
+#[derive(Clone, Debug)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    moves: [u32; 2],
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        Clock { control, remaining: [control.base; 2], moves: [0; 2] }
+    }
+
+    pub fn remaining(&self, color: Color) -> Duration {
+        self.remaining[color.to_index()]
+    }
+
+    pub fn time_left(&self, color: Color) -> TimeLeft {
+        TimeLeft {
+            remaining: self.remaining(color),
+            opponent: self.remaining(!color),
+            increment: self.control.increment,
+            moves_to_go: self.control.moves.map(|m| m - self.moves[color.to_index()] % m),
+        }
+    }
+
+    /// Charge a move's thinking time to `color`. Returns false if their flag
+    /// fell, otherwise adds the increment and any new session's time.
+    pub fn punch(&mut self, color: Color, elapsed: Duration) -> bool {
+        let side = color.to_index();
+        if elapsed > self.remaining[side] {
+            self.remaining[side] = Duration::ZERO;
+            return false;
+        }
+        self.remaining[side] = self.remaining[side] - elapsed + self.control.increment;
+        self.moves[side] += 1;
+        if let Some(moves) = self.control.moves {
+            if self.moves[side].is_multiple_of(moves) {
+                self.remaining[side] += self.control.base;
+            }
+        }
+        true
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "White {}  Black {}", format_time(self.remaining(Color::White)), format_time(self.remaining(Color::Black)))
+    }
+}
+
+/// `m:ss.t`, or `h:mm:ss` for an hour or more
+pub fn format_time(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    let seconds = tenths / 10;
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}.{}", seconds / 60, seconds % 60, tenths % 10)
+    }
+}
+
+/// Whether `color` could checkmate by any series of legal moves, with the
+/// other side helping. Running out of time against a side that can't is a
+/// draw rather than a loss. A lone knight or same coloured bishops can only
+/// mate a king hemmed in by its own pieces, so those count too.
+pub fn can_mate(board: &Board, color: Color) -> bool {
+    let mine = board.color_combined(color);
+    let theirs = board.color_combined(!color) & !board.pieces(Piece::King);
+    let heavy = (board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen)) & mine;
+    let knights = board.pieces(Piece::Knight) & mine;
+    let bishops = board.pieces(Piece::Bishop) & mine;
+    if heavy.popcnt() > 0 || (knights.popcnt() > 0 && (knights | bishops).popcnt() > 1) {
+        return true;
+    }
+    if knights.popcnt() > 0 {
+        return theirs.popcnt() > 0;
+    }
+    // bishops only give check on their own colour, so the blockers are needed on the other
+    let light = |bits: BitBoard| bits.filter(|square| (square.get_rank().to_index() + square.get_file().to_index()) % 2 == 1).count();
+    let light_bishops = light(bishops);
+    let dark_bishops = bishops.popcnt() as usize - light_bishops;
+    if light_bishops > 0 && dark_bishops > 0 {
+        return true;
+    }
+    let their_bishops = board.pieces(Piece::Bishop) & theirs;
+    let their_light = light(their_bishops);
+    let their_dark = their_bishops.popcnt() as usize - their_light;
+    let blockers = (theirs & !their_bishops).popcnt() > 0 || if light_bishops > 0 { their_dark > 0 } else { their_light > 0 };
+    bishops.popcnt() > 0 && blockers
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::time::Duration;
+    use chess::{Board, Color};
+    use crate::clock::{can_mate, format_time, Clock, TimeControl};
+
+    #[test]
+    fn parses_controls() {
+        assert_eq!(TimeControl::from_str("300+2").unwrap(), TimeControl {
+            base: Duration::from_secs(300), increment: Duration::from_secs(2), moves: None,
+        });
+        assert_eq!(TimeControl::from_str("40/5400").unwrap(), TimeControl {
+            base: Duration::from_secs(5400), increment: Duration::ZERO, moves: Some(40),
+        });
+        assert_eq!(TimeControl::from_str("0.5+0.1").unwrap().base, Duration::from_millis(500));
+        assert!(TimeControl::from_str("fast").is_err());
+        assert!(TimeControl::from_str("0/60").is_err());
+    }
+
+    #[test]
+    fn increment_and_flag() {
+        let mut clock = Clock::new(TimeControl::from_str("10+1").unwrap());
+        assert!(clock.punch(Color::White, Duration::from_secs(4)));
+        assert_eq!(clock.remaining(Color::White), Duration::from_secs(7));
+        assert_eq!(clock.time_left(Color::Black).opponent, Duration::from_secs(7));
+        assert!(!clock.punch(Color::Black, Duration::from_secs(11)));
+        assert_eq!(clock.remaining(Color::Black), Duration::ZERO);
+    }
+
+    #[test]
+    fn sessions_add_time() {
+        let mut clock = Clock::new(TimeControl::from_str("2/60").unwrap());
+        assert_eq!(clock.time_left(Color::White).moves_to_go, Some(2));
+        clock.punch(Color::White, Duration::from_secs(10));
+        assert_eq!(clock.time_left(Color::White).moves_to_go, Some(1));
+        clock.punch(Color::White, Duration::from_secs(10));
+        assert_eq!(clock.remaining(Color::White), Duration::from_secs(100));
+        assert_eq!(clock.time_left(Color::White).moves_to_go, Some(2));
+    }
+
+    #[test]
+    fn formats_times() {
+        assert_eq!(format_time(Duration::from_millis(65_400)), "1:05.4");
+        assert_eq!(format_time(Duration::from_secs(3725)), "1:02:05");
+    }
+
+    #[test]
+    fn mating_material() {
+        assert!(can_mate(&Board::default(), Color::White));
+        let board = Board::from_str("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert!(!can_mate(&board, Color::White));
+        assert!(!can_mate(&board, Color::Black));
+        // a knight against a pawn can still mate, the pawn blocking its own king
+        let board = Board::from_str("4k3/4p3/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert!(can_mate(&board, Color::White));
+        assert!(can_mate(&board, Color::Black));
+        // a bishop needs a blocker that can stand on the other colour
+        let same = Board::from_str("4k3/8/8/8/8/8/8/2B1K1b1 w - - 0 1").unwrap();
+        assert!(!can_mate(&same, Color::White));
+        let other = Board::from_str("4k3/8/8/8/8/8/8/2B1Kb2 w - - 0 1").unwrap();
+        assert!(can_mate(&other, Color::White));
+        let bishops = Board::from_str("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+        assert!(can_mate(&bishops, Color::White));
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+use chess::{Board, ChessMove};
+use crate::nn::ChessNet;
+use crate::openings::{parse_line, OPENINGS};
+use crate::weights::distance;
+
+/// How different the members of one generation are from each other
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diversity {
+    /// Mean Euclidean distance between every pair of members' weights
+    pub distance: f64,
+    /// Mean fraction of probe positions where a pair of members pick the same move
+    pub agreement: f64,
+    pub unique_games: usize,
+    pub games: usize,
+}
+
+impl Diversity {
+    pub fn measure(members: &[ChessNet], games: &[Vec<ChessMove>]) -> Diversity {
+        let pairs: Vec<(usize, usize)> = (0..members.len())
+            .flat_map(|i| (i + 1..members.len()).map(move |j| (i, j)))
+            .collect();
+        let probes = probe_positions();
+        // every member's choice in every probe position
+        let choices: Vec<Vec<Option<ChessMove>>> = members.iter()
+            .map(|member| probes.iter().map(|board| member.ranked_moves(board).first().map(|&(m, _)| m)).collect())
+            .collect();
+        let mean = |values: Vec<f64>| if values.is_empty() { 0. } else { values.iter().sum::<f64>() / values.len() as f64 };
+        let distance = mean(pairs.iter().map(|&(i, j)| distance(&members[i], &members[j])).collect());
+        let agreement = mean(pairs.iter()
+            .map(|&(i, j)| {
+                let same = choices[i].iter().zip(&choices[j]).filter(|(a, b)| a == b).count();
+                same as f64 / probes.len() as f64
+            })
+            .collect());
+        Diversity { distance, agreement, unique_games: unique_games(games), games: games.len() }
+    }
+}
+
+/// Positions after each book opening, the same for every generation
+pub fn probe_positions() -> Vec<Board> {
+    OPENINGS.iter()
+        .map(|line| parse_line(line).into_iter().fold(Board::default(), |board, m| board.make_move_new(m)))
+        .collect()
+}
+
+/// Number of distinct move sequences
+pub fn unique_games(games: &[Vec<ChessMove>]) -> usize {
+    games.iter().collect::<HashSet<_>>().len()
+}
+
+
+#[cfg(test)]
+mod test {
+    use candle_nn::VarMap;
+    use chess::{ChessMove, Square};
+    use crate::diversity::{probe_positions, unique_games, Diversity};
+    use crate::nn::ChessNet;
+
+    #[test]
+    fn clones_have_no_diversity() {
+        let model = ChessNet::new(VarMap::new());
+        let file = std::env::temp_dir().join("chessers_diversity_clone.safetensors");
+        model.save(file.to_str().unwrap().to_string());
+        let clones = [model, ChessNet::from_file(file.to_str().unwrap())];
+        let diversity = Diversity::measure(&clones, &[]);
+        assert_eq!(diversity.distance, 0.);
+        assert_eq!(diversity.agreement, 1.);
+
+        let strangers = [ChessNet::new(VarMap::new()), ChessNet::new(VarMap::new())];
+        assert!(Diversity::measure(&strangers, &[]).distance > 0.);
+    }
+
+    #[test]
+    fn counts_unique_games() {
+        let e4 = ChessMove::new(Square::E2, Square::E4, None);
+        let d4 = ChessMove::new(Square::D2, Square::D4, None);
+        assert_eq!(unique_games(&[vec![e4], vec![e4], vec![d4], vec![e4, d4]]), 3);
+        assert_eq!(probe_positions().len(), 25);
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::Path;
+use rand::thread_rng;
+use crate::adjudication::Adjudication;
+use crate::arena::play_from;
+use crate::clock::TimeControl;
+use crate::nn::ChessNet;
+use crate::openings;
+use crate::rating::{player_id, RatingsDb, Record};
+
+/// One model's results against each of a pool of opponents
+pub struct Gauntlet {
+    pub model: String,
+    /// Opponent file and the model's record against it
+    pub results: Vec<(String, Record)>,
+}
+
+impl Gauntlet {
+    /// Play `games` games against every opponent. Games come in pairs that
+    /// share a sampled opening, with the model taking each colour once.
+    pub fn run(model_file: &str, opponent_files: &[String], games: usize, adjudication: &Adjudication,
+               time_control: Option<TimeControl>, ratings: &mut RatingsDb) -> Gauntlet {
+        let model = ChessNet::from_file(model_file);
+        let model_id = player_id(model_file);
+        let mut results = vec!();
+        for opponent_file in opponent_files {
+            let opponent = ChessNet::from_file(opponent_file);
+            let opponent_id = player_id(opponent_file);
+            let lines = openings::sample(games.div_ceil(2), &mut thread_rng());
+            let mut record = Record::default();
+            for game in 0..games {
+                let opening = &lines[game / 2];
+                let score = if game % 2 == 0 {
+                    let white_score = play_from(&model, &opponent, opening, adjudication, time_control).outcome.white_score();
+                    ratings.record(&model_id, &opponent_id, white_score);
+                    white_score
+                } else {
+                    let white_score = play_from(&opponent, &model, opening, adjudication, time_control).outcome.white_score();
+                    ratings.record(&opponent_id, &model_id, white_score);
+                    1. - white_score
+                };
+                record.add(score);
+            }
+            results.push((opponent_file.clone(), record));
+        }
+        ratings.commit();
+        Gauntlet { model: model_file.to_string(), results }
+    }
+
+    /// Combined record against the whole pool
+    pub fn total(&self) -> Record {
+        let mut total = Record::default();
+        for (_, record) in &self.results {
+            total += *record;
+        }
+        total
+    }
+
+    pub fn print(&self) {
+        println!("Gauntlet for {}", self.model);
+        println!("{:>5} {:>5} {:>5} {:>7} {:>16}  Opponent", "W", "D", "L", "Score", "Elo");
+        let row = |name: &str, record: &Record| {
+            println!("{:>5} {:>5} {:>5} {:>6.1}% {:>+7.0} ± {:<6.0}  {}",
+                     record.wins, record.draws, record.losses, 100. * record.score(),
+                     record.elo(), record.elo_error(), name);
+        };
+        for (opponent, record) in &self.results {
+            row(opponent, record);
+        }
+        row("Total", &self.total());
+    }
+}
+
+/// Every safetensors file in a directory, or every file matching a glob
+pub fn find_opponents(source: &str) -> Vec<String> {
+    let pattern = if Path::new(source).is_dir() {
+        format!("{}/*.safetensors", source.trim_end_matches('/'))
+    } else {
+        source.to_string()
+    };
+    let mut files: Vec<String> = glob::glob(&pattern)
+        .unwrap_or_else(|e| panic!("Invalid opponent pattern {}: {}", source, e))
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    files
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::fs::create_dir_all;
+    use candle_nn::VarMap;
+    use crate::adjudication::Adjudication;
+    use crate::gauntlet::{find_opponents, Gauntlet};
+    use crate::nn::ChessNet;
+    use crate::rating::RatingsDb;
+
+    #[test]
+    fn plays_every_opponent() {
+        let dir = std::env::temp_dir().join("chessers_gauntlet_test");
+        create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap();
+        for name in ["a", "b", "c"] {
+            ChessNet::new(VarMap::new()).save(format!("{}/{}.safetensors", dir, name));
+        }
+        let opponents = find_opponents(dir);
+        assert_eq!(opponents.len(), 3);
+        assert_eq!(find_opponents(&format!("{}/[ab].safetensors", dir)).len(), 2);
+
+        let mut ratings = RatingsDb::open(format!("{}/ratings.csv", dir));
+        let gauntlet = Gauntlet::run(&opponents[0], &opponents[1..], 3, &Adjudication::default(), None, &mut ratings);
+        assert_eq!(gauntlet.results.len(), 2);
+        assert_eq!(gauntlet.total().games(), 6);
+        gauntlet.print();
+    }
+}
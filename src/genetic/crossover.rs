@@ -0,0 +1,129 @@
+use std::fmt::Debug;
+use candle_core::{Result, Tensor};
+use rand::{thread_rng, Rng};
+
+/// How a child's tensors are assembled from its two parents.
+///
+/// Both parents list their tensors in the same order with matching shapes.
+/// `bias` is the chance of inheriting any given piece from `first`, so a
+/// value above 0.5 favours the first parent.
+pub trait Crossover: Debug {
+    fn cross(&self, first: &[Tensor], second: &[Tensor], bias: f64) -> Result<Vec<Tensor>>;
+}
+
+/// Pick every scalar independently from either parent.
+#[derive(Debug)]
+pub struct Uniform;
+
+impl Crossover for Uniform {
+    fn cross(&self, first: &[Tensor], second: &[Tensor], bias: f64) -> Result<Vec<Tensor>> {
+        first.iter().zip(second).map(|(a, b)| {
+            a.rand_like(0., 1.)?.lt(bias)?.where_cond(a, b)
+        }).collect()
+    }
+}
+
+/// Pick whole slices along the first dimension, so each filter (or input
+/// channel for a transposed convolution) keeps its learned kernel intact.
+#[derive(Debug)]
+pub struct PerFilter;
+
+impl Crossover for PerFilter {
+    fn cross(&self, first: &[Tensor], second: &[Tensor], bias: f64) -> Result<Vec<Tensor>> {
+        first.iter().zip(second).map(|(a, b)| {
+            // one coin per filter, broadcast over the rest of the tensor
+            let coin_shape: Vec<usize> = a.dims().iter().enumerate()
+                .map(|(i, &d)| if i == 0 { d } else { 1 })
+                .collect();
+            let coins = Tensor::rand(0f64, 1., coin_shape, a.device())?
+                .to_dtype(a.dtype())?
+                .lt(bias)?
+                .broadcast_as(a.shape())?
+                .contiguous()?;
+            coins.where_cond(a, b)
+        }).collect()
+    }
+}
+
+/// Weighted average of both parents: `bias * first + (1 - bias) * second`.
+#[derive(Debug)]
+pub struct Blend;
+
+impl Crossover for Blend {
+    fn cross(&self, first: &[Tensor], second: &[Tensor], bias: f64) -> Result<Vec<Tensor>> {
+        first.iter().zip(second).map(|(a, b)| {
+            a.affine(bias, 0.)? + b.affine(1. - bias, 0.)?
+        }).collect()
+    }
+}
+
+/// Cut the tensor list at one random point, taking everything before the cut
+/// from one parent and everything after from the other.
+#[derive(Debug)]
+pub struct SinglePoint;
+
+impl Crossover for SinglePoint {
+    fn cross(&self, first: &[Tensor], second: &[Tensor], bias: f64) -> Result<Vec<Tensor>> {
+        let mut rng = thread_rng();
+        let cut = rng.gen_range(0..=first.len());
+        let (head, tail) = if rng.gen_bool(bias.clamp(0., 1.)) { (first, second) } else { (second, first) };
+        Ok(head[..cut].iter().chain(&tail[cut..]).cloned().collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use candle_core::{Device, DType, Tensor};
+    use crate::genetic::crossover::{Blend, Crossover, PerFilter, SinglePoint, Uniform};
+
+    fn parents() -> (Vec<Tensor>, Vec<Tensor>) {
+        let first = vec![
+            Tensor::ones((2, 6, 3, 3), DType::F64, &Device::Cpu).unwrap(),
+            Tensor::ones(2, DType::F64, &Device::Cpu).unwrap(),
+        ];
+        let second = first.iter().map(|t| t.zeros_like().unwrap()).collect();
+        (first, second)
+    }
+
+    fn total(tensor: &Tensor) -> f64 {
+        tensor.sum_all().unwrap().to_scalar().unwrap()
+    }
+
+    #[test]
+    fn certain_bias_copies_first_parent() {
+        let (first, second) = parents();
+        for crossover in [&Uniform as &dyn Crossover, &PerFilter, &Blend] {
+            let child = crossover.cross(&first, &second, 1.).unwrap();
+            for (c, f) in child.iter().zip(&first) {
+                assert_eq!(c.dims(), f.dims());
+                assert_eq!(total(c), total(f), "{:?}", crossover);
+            }
+        }
+    }
+
+    #[test]
+    fn per_filter_keeps_kernels_whole() {
+        let (first, second) = parents();
+        let child = PerFilter.cross(&first, &second, 0.5).unwrap();
+        for filter in 0..2 {
+            let sum = total(&child[0].get(filter).unwrap());
+            assert!(sum == 0. || sum == 54., "filter {} was split: {}", filter, sum);
+        }
+    }
+
+    #[test]
+    fn blend_averages() {
+        let (first, second) = parents();
+        let child = Blend.cross(&first, &second, 0.25).unwrap();
+        assert_eq!(total(&child[1]), 0.5);
+    }
+
+    #[test]
+    fn single_point_keeps_tensors_whole() {
+        let (first, second) = parents();
+        let child = SinglePoint.cross(&first, &second, 0.5).unwrap();
+        let sum = total(&child[0]);
+        assert!(sum == 0. || sum == 108.);
+    }
+}
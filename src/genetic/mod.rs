@@ -0,0 +1,5 @@
+//! Operators used by the [`Arena`](crate::arena::Arena) to breed each new generation.
+
+pub mod crossover;
+pub mod mutation;
+pub mod selection;
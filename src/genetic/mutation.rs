@@ -0,0 +1,118 @@
+use candle_core::{Result, Tensor};
+
+/// How a child's weights are perturbed after crossover.
+///
+/// Every operator picks each weight independently with probability `rate`,
+/// so unpicked weights are inherited unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mutation {
+    /// Multiply the weight by 0.5 or 2. Can never flip a sign or move a zero weight.
+    Scale { rate: f64 },
+    /// Add gaussian noise with standard deviation `sigma`.
+    Gaussian { rate: f64, sigma: f64 },
+    /// Throw the weight away and draw a fresh one from N(0, sigma).
+    Reset { rate: f64, sigma: f64 },
+    /// Negate the weight.
+    SignFlip { rate: f64 },
+    /// Gaussian noise with a step size that is carried per layer and
+    /// mutated log-normally every generation, starting from `sigma`.
+    Adaptive { rate: f64, sigma: f64 },
+}
+
+impl Default for Mutation {
+    fn default() -> Self {
+        Mutation::Scale { rate: 0.2 }
+    }
+}
+
+impl Mutation {
+    /// Starting step size for a layer that has not been mutated yet.
+    pub fn initial_step_size(&self) -> f64 {
+        match self {
+            Mutation::Gaussian { sigma, .. }
+            | Mutation::Reset { sigma, .. }
+            | Mutation::Adaptive { sigma, .. } => *sigma,
+            Mutation::Scale { .. } | Mutation::SignFlip { .. } => 1.,
+        }
+    }
+
+    /// Mutate `weights`, returning the new tensor.
+    ///
+    /// `step_size` is the layer's current step size and is updated in place by
+    /// [`Mutation::Adaptive`]; the other operators leave it alone.
+    pub fn mutate(&self, weights: &Tensor, step_size: &mut f64) -> Result<Tensor> {
+        let (rate, mutated) = match self {
+            Mutation::Scale { rate } => {
+                let coin = weights.rand_like(0., 1.)?.lt(0.5)?;
+                let halve = weights.ones_like()?.affine(0.5, 0.)?;
+                let double = weights.ones_like()?.affine(2., 0.)?;
+                let factor = coin.where_cond(&halve, &double)?;
+                (*rate, (weights * factor)?)
+            },
+            Mutation::Gaussian { rate, sigma } => (*rate, (weights + weights.randn_like(0., *sigma)?)?),
+            Mutation::Reset { rate, sigma } => (*rate, weights.randn_like(0., *sigma)?),
+            Mutation::SignFlip { rate } => (*rate, weights.neg()?),
+            Mutation::Adaptive { rate, .. } => {
+                // one step size per layer, so the learning rate is 1/sqrt(n)
+                let tau = 1. / (weights.elem_count() as f64).sqrt();
+                let noise: f64 = Tensor::randn(0f64, 1., (), weights.device())?.to_scalar()?;
+                *step_size *= (tau * noise).exp();
+                (*rate, (weights + weights.randn_like(0., *step_size)?)?)
+            },
+        };
+        let mask = weights.rand_like(0., 1.)?.lt(rate)?;
+        mask.where_cond(&mutated, weights)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use candle_core::{Device, DType, Tensor};
+    use crate::genetic::mutation::Mutation;
+
+    fn weights() -> Tensor {
+        Tensor::ones((2, 6, 3, 3), DType::F64, &Device::Cpu).unwrap()
+    }
+
+    #[test]
+    fn zero_rate_is_identity() {
+        let w = weights();
+        let mut step = 1.;
+        for mutation in [
+            Mutation::Scale { rate: 0. },
+            Mutation::Gaussian { rate: 0., sigma: 1. },
+            Mutation::Reset { rate: 0., sigma: 1. },
+            Mutation::SignFlip { rate: 0. },
+            Mutation::Adaptive { rate: 0., sigma: 1. },
+        ] {
+            let mutated = mutation.mutate(&w, &mut step).unwrap();
+            let diff: f64 = (mutated - &w).unwrap().abs().unwrap().sum_all().unwrap().to_scalar().unwrap();
+            assert_eq!(diff, 0., "{:?}", mutation);
+        }
+    }
+
+    #[test]
+    fn sign_flip_negates_everything() {
+        let w = weights();
+        let mutated = Mutation::SignFlip { rate: 1. }.mutate(&w, &mut 1.).unwrap();
+        let total: f64 = mutated.sum_all().unwrap().to_scalar().unwrap();
+        assert_eq!(total, -(w.elem_count() as f64));
+    }
+
+    #[test]
+    fn gaussian_moves_zero_weights() {
+        let w = weights().zeros_like().unwrap();
+        let mutated = Mutation::Gaussian { rate: 1., sigma: 0.1 }.mutate(&w, &mut 0.1).unwrap();
+        let moved: f64 = mutated.abs().unwrap().sum_all().unwrap().to_scalar().unwrap();
+        assert!(moved > 0.);
+    }
+
+    #[test]
+    fn adaptive_updates_step_size() {
+        let mut step = 0.1;
+        Mutation::Adaptive { rate: 1., sigma: 0.1 }.mutate(&weights(), &mut step).unwrap();
+        assert_ne!(step, 0.1);
+        assert!(step > 0.);
+    }
+}
@@ -0,0 +1,84 @@
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+
+/// How breeding partners are picked from a generation's fitness totals.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Selection {
+    /// Chance of being picked is proportional to fitness.
+    #[default]
+    Roulette,
+    /// Draw `size` members at random and pick the fittest of them.
+    Tournament { size: usize },
+    /// Chance of being picked is proportional to rank, so one dominant
+    /// member can't take over the whole population.
+    Rank,
+}
+
+impl Selection {
+    /// Index of the selected member
+    pub fn select<R: Rng>(&self, fitness: &[f64], rng: &mut R) -> usize {
+        match self {
+            Selection::Roulette => match WeightedIndex::new(fitness) {
+                Ok(dist) => dist.sample(rng),
+                // everybody scored zero, so nobody deserves more of a chance
+                Err(_) => rng.gen_range(0..fitness.len()),
+            },
+            Selection::Tournament { size } => (0..(*size).max(1))
+                .map(|_| rng.gen_range(0..fitness.len()))
+                .max_by(|&a, &b| fitness[a].total_cmp(&fitness[b]))
+                .unwrap(),
+            Selection::Rank => {
+                let ranked = ranking(fitness);
+                let weights: Vec<usize> = (1..=fitness.len()).rev().collect();
+                ranked[WeightedIndex::new(weights).unwrap().sample(rng)]
+            },
+        }
+    }
+}
+
+/// Member indices sorted from fittest to weakest
+pub fn ranking(fitness: &[f64]) -> Vec<usize> {
+    let mut ranked: Vec<usize> = (0..fitness.len()).collect();
+    ranked.sort_by(|&a, &b| fitness[b].total_cmp(&fitness[a]));
+    ranked
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::thread_rng;
+    use crate::genetic::selection::{ranking, Selection};
+
+    #[test]
+    fn ranks_fittest_first() {
+        assert_eq!(ranking(&[1., 3., 2.]), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn roulette_survives_all_zero() {
+        let index = Selection::Roulette.select(&[0., 0., 0.], &mut thread_rng());
+        assert!(index < 3);
+    }
+
+    #[test]
+    fn roulette_never_picks_zero_fitness() {
+        for _ in 0..100 {
+            assert_eq!(Selection::Roulette.select(&[0., 5., 0.], &mut thread_rng()), 1);
+        }
+    }
+
+    #[test]
+    fn big_tournament_picks_the_best() {
+        let selection = Selection::Tournament { size: 100 };
+        for _ in 0..10 {
+            assert_eq!(selection.select(&[1., 2., 9., 3.], &mut thread_rng()), 2);
+        }
+    }
+
+    #[test]
+    fn rank_never_picks_outside_population() {
+        for _ in 0..100 {
+            assert!(Selection::Rank.select(&[4., 1.], &mut thread_rng()) < 2);
+        }
+    }
+}
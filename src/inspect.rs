@@ -0,0 +1,172 @@
+use std::fmt::Write;
+use chess::{Board, ChessMove, Square, ALL_SQUARES};
+use crate::nn::ChessNet;
+use crate::pgn::san;
+use crate::ui::glyph;
+
+/// Titles of the two output channels
+pub const CHANNELS: [&str; 2] = ["Move away from", "Move to"];
+
+/// A legal move and how the two channels add up to its score
+pub struct MoveScore {
+    pub chess_move: ChessMove,
+    pub san: String,
+    pub source: f64,
+    pub dest: f64,
+}
+
+impl MoveScore {
+    pub fn total(&self) -> f64 {
+        self.source + self.dest
+    }
+}
+
+/// What a model makes of one position
+pub struct Inspection {
+    pub board: Board,
+    /// Output channels indexed by rank then file
+    pub maps: [[[f64; 8]; 8]; 2],
+    /// Legal moves, best first
+    pub moves: Vec<MoveScore>,
+}
+
+impl Inspection {
+    pub fn new(model: &ChessNet, board: Board) -> Inspection {
+        let maps = model.heatmaps(&board);
+        let value = |channel: usize, square: Square| maps[channel][square.get_rank().to_index()][square.get_file().to_index()];
+        let moves = model.ranked_moves(&board).into_iter()
+            .map(|(chess_move, _)| MoveScore {
+                chess_move,
+                san: san(&board, chess_move),
+                source: value(0, chess_move.get_source()),
+                dest: value(1, chess_move.get_dest()),
+            })
+            .collect();
+        Inspection { board, maps, moves }
+    }
+
+    /// Both channels as shaded terminal grids, White at the bottom, then the top moves
+    pub fn render(&self, top: usize) -> String {
+        let mut output = String::new();
+        for (title, map) in CHANNELS.iter().zip(&self.maps) {
+            output.push_str(&heatmap(title, map, &self.board));
+            output.push('\n');
+        }
+        writeln!(output, "{:>4}  {:<8}{:<6}{:>9}{:>9}{:>9}", "Rank", "Move", "UCI", "From", "To", "Score").unwrap();
+        for (rank, score) in self.moves.iter().take(top).enumerate() {
+            writeln!(output, "{:>4}  {:<8}{:<6}{:>9.4}{:>9.4}{:>9.4}", rank + 1, score.san, score.chess_move.to_string(),
+                     score.source, score.dest, score.total()).unwrap();
+        }
+        output
+    }
+
+    /// Both channels side by side as an SVG image, shaded like the terminal maps
+    pub fn svg(&self) -> String {
+        const CELL: usize = 48;
+        const MARGIN: usize = 24;
+        let width = 2 * (8 * CELL + 2 * MARGIN);
+        let height = 8 * CELL + 3 * MARGIN;
+        let mut svg = String::new();
+        writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif">"#, width, height).unwrap();
+        for (channel, (title, map)) in CHANNELS.iter().zip(&self.maps).enumerate() {
+            let left = channel * (8 * CELL + 2 * MARGIN) + MARGIN;
+            let (low, high) = bounds(map);
+            writeln!(svg, r#"<text x="{}" y="{}" font-size="16">{} ({:.3} to {:.3})</text>"#, left, MARGIN - 6, title, low, high).unwrap();
+            for square in ALL_SQUARES {
+                let (rank, file) = (square.get_rank().to_index(), square.get_file().to_index());
+                let shade = (255. * (1. - scale(map[rank][file], low, high))).round() as u8;
+                let (x, y) = (left + file * CELL, MARGIN + (7 - rank) * CELL);
+                writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="rgb(255,{},{})" stroke="gray"/>"#, x, y, CELL, CELL, shade, shade).unwrap();
+                if let Some((piece, color)) = self.board.piece_on(square).zip(self.board.color_on(square)) {
+                    writeln!(svg, r#"<text x="{}" y="{}" font-size="30" text-anchor="middle">{}</text>"#, x + CELL / 2, y + CELL * 2 / 3, glyph(piece, color)).unwrap();
+                }
+                writeln!(svg, r#"<text x="{}" y="{}" font-size="9" text-anchor="end">{:.2}</text>"#, x + CELL - 2, y + CELL - 3, map[rank][file]).unwrap();
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Lowest and highest value in a map
+fn bounds(map: &[[f64; 8]; 8]) -> (f64, f64) {
+    map.iter().flatten().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &v| (low.min(v), high.max(v)))
+}
+
+/// Where a value sits between `low` and `high`, from 0 to 1
+fn scale(value: f64, low: f64, high: f64) -> f64 {
+    if high > low { (value - low) / (high - low) } else { 0. }
+}
+
+/// One channel on the 256 colour greyscale ramp, brightest for the highest
+/// value, with each square's value and piece letter
+fn heatmap(title: &str, map: &[[f64; 8]; 8], board: &Board) -> String {
+    let (low, high) = bounds(map);
+    let mut output = format!("{} ({:.4} to {:.4})\n", title, low, high);
+    for rank in (0..8).rev() {
+        output.push_str(&format!("{} ", rank + 1));
+        for (file, &value) in map[rank].iter().enumerate() {
+            let level = scale(value, low, high);
+            let background = 232 + (level * 23.).round() as u8;
+            let foreground = if level > 0.5 { 16 } else { 231 };
+            let square = ALL_SQUARES[rank * 8 + file];
+            let piece = board.piece_on(square)
+                .zip(board.color_on(square))
+                .map_or(' ', |(piece, color)| piece.to_string(color).chars().next().unwrap());
+            output.push_str(&format!("\x1b[48;5;{};38;5;{}m{}{:>6.2} \x1b[0m", background, foreground, piece, value));
+        }
+        output.push('\n');
+    }
+    output.push_str("  ");
+    for file in "abcdefgh".chars() {
+        output.push_str(&format!("{:^8}", file));
+    }
+    output.push('\n');
+    output
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use candle_nn::VarMap;
+    use chess::{Board, Square};
+    use crate::inspect::{bounds, heatmap, Inspection};
+    use crate::nn::ChessNet;
+
+    #[test]
+    fn channels_add_up_to_the_score() {
+        let model = ChessNet::new(VarMap::new());
+        let inspection = Inspection::new(&model, Board::default());
+        let ranked = model.ranked_moves(&Board::default());
+        assert_eq!(inspection.moves.len(), 20);
+        for (score, (chess_move, total)) in inspection.moves.iter().zip(ranked) {
+            assert_eq!(score.chess_move, chess_move);
+            assert!((score.total() - total).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn heatmap_layout() {
+        let mut map = [[0.; 8]; 8];
+        map[0][0] = 2.;
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let rendered = heatmap("Move to", &map, &board);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "Move to (0.0000 to 2.0000)");
+        assert!(lines[8].starts_with("1 \x1b[48;5;255;38;5;16mR  2.00 "));
+        assert!(lines[9].contains("a"));
+        assert_eq!(bounds(&map), (0., 2.));
+    }
+
+    #[test]
+    fn svg_has_every_square() {
+        let model = ChessNet::new(VarMap::new());
+        let inspection = Inspection::new(&model, Board::default());
+        let svg = inspection.svg();
+        assert_eq!(svg.matches("<rect").count(), 128);
+        assert_eq!(svg.matches('♔').count(), 2);
+        assert!(inspection.render(3).contains(&inspection.moves[0].san));
+        assert_eq!(inspection.maps[0][Square::E2.get_rank().to_index()].len(), 8);
+    }
+}
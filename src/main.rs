@@ -4,8 +4,27 @@ mod playground;
 mod player;
 mod ui;
 mod nn;
+mod adjudication;
 mod arena;
+mod baseline;
 mod cli;
+mod clock;
+mod diversity;
+mod gauntlet;
+mod genetic;
+mod inspect;
+mod metrics;
+mod openings;
+mod pgn;
+mod progress;
+mod rating;
+mod spec;
+mod sprt;
+mod time_manager;
+mod tournament;
+mod tui;
+mod web;
+mod weights;
 
 fn main() {
     let start = chrono::Utc::now();
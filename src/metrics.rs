@@ -0,0 +1,280 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use serde::{Deserialize, Serialize};
+use crate::arena::GameRecord;
+
+/// File in every run directory with one JSON line of metrics per epoch
+pub const METRICS_FILE: &str = "metrics.jsonl";
+
+/// Widest an ASCII plot gets, in columns
+const MAX_WIDTH: usize = 60;
+
+/// What happened in one epoch of training
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EpochMetrics {
+    pub epoch: i32,
+    /// Every member's fitness
+    pub fitness: Vec<f64>,
+    /// Round robin white wins, draws and black wins
+    pub results: [u32; 3],
+    pub games: usize,
+    /// Mean round robin game length in plies
+    pub average_length: f64,
+    /// Number of round robin games ending each way
+    pub terminations: BTreeMap<String, u32>,
+    /// Wall time of the whole epoch in seconds
+    pub seconds: f64,
+    pub champion: usize,
+    /// Champion's points against every earlier champion, from `history_games` games
+    pub history_score: f64,
+    pub history_games: usize,
+    /// Champion's score rate against each benchmark opponent
+    #[serde(default)]
+    pub benchmarks: BTreeMap<String, f64>,
+}
+
+impl EpochMetrics {
+    pub fn new(epoch: i32) -> EpochMetrics {
+        EpochMetrics { epoch, ..Default::default() }
+    }
+
+    /// Add a round robin game
+    pub fn record(&mut self, game: &GameRecord) {
+        self.games += 1;
+        self.average_length += (game.moves.len() as f64 - self.average_length) / self.games as f64;
+        self.results[game.outcome as usize] += 1;
+        *self.terminations.entry(game.termination.to_string()).or_default() += 1;
+    }
+
+    pub fn best_fitness(&self) -> f64 {
+        self.fitness.iter().cloned().fold(0., f64::max)
+    }
+
+    pub fn mean_fitness(&self) -> f64 {
+        if self.fitness.is_empty() { 0. } else { self.fitness.iter().sum::<f64>() / self.fitness.len() as f64 }
+    }
+
+    pub fn worst_fitness(&self) -> f64 {
+        self.fitness.iter().cloned().fold(1., f64::min)
+    }
+
+    /// Champion's score rate against earlier champions, None in the first epoch
+    pub fn history_rate(&self) -> Option<f64> {
+        (self.history_games > 0).then(|| self.history_score / self.history_games as f64)
+    }
+}
+
+/// Add one epoch to a run's metrics file
+pub fn append(file: &str, metrics: &EpochMetrics) {
+    let mut writer = OpenOptions::new().create(true).append(true).open(file)
+        .unwrap_or_else(|e| panic!("Failed to open {} for writing: {}", file, e));
+    let line = serde_json::to_string(metrics).expect("Couldn't serialise metrics");
+    writeln!(writer, "{}", line).unwrap_or_else(|e| panic!("Failed to write {}: {}", file, e));
+}
+
+/// Every epoch in a run's metrics file
+pub fn load(file: &str) -> Vec<EpochMetrics> {
+    let text = std::fs::read_to_string(file).unwrap_or_else(|e| panic!("Couldn't read {}: {}", file, e));
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("Bad line in {}: {}", file, e)))
+        .collect()
+}
+
+/// A named series of (epoch, value) points
+pub struct Curve {
+    pub title: String,
+    pub points: Vec<(i32, f64)>,
+}
+
+/// The curves worth plotting for a run
+pub fn curves(metrics: &[EpochMetrics]) -> Vec<Curve> {
+    let curve = |title: &str, value: &dyn Fn(&EpochMetrics) -> Option<f64>| Curve {
+        title: title.to_string(),
+        points: metrics.iter().filter_map(|m| value(m).map(|v| (m.epoch, v))).collect(),
+    };
+    let opponents: BTreeSet<&String> = metrics.iter().flat_map(|m| m.benchmarks.keys()).collect();
+    let mut curves = vec![
+        curve("Best fitness", &|m| Some(m.best_fitness())),
+        curve("Mean fitness", &|m| Some(m.mean_fitness())),
+        curve("Champion score against earlier champions", &|m| m.history_rate()),
+        curve("Draw rate", &|m| (m.games > 0).then(|| m.results[1] as f64 / m.games as f64)),
+        curve("Average game length (plies)", &|m| Some(m.average_length)),
+        curve("Epoch time (s)", &|m| Some(m.seconds)),
+    ];
+    curves.extend(opponents.into_iter()
+        .map(|name| curve(&format!("Champion score against {}", name), &|m| m.benchmarks.get(name).copied())));
+    curves
+}
+
+/// Lowest and highest value of a curve
+fn bounds(points: &[(i32, f64)]) -> (f64, f64) {
+    points.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &(_, v)| (low.min(v), high.max(v)))
+}
+
+/// Where a value sits between `low` and `high`, from 0 to 1
+fn scale(value: f64, low: f64, high: f64) -> f64 {
+    if high > low { (value - low) / (high - low) } else { 0.5 }
+}
+
+/// A curve as a scatter of stars, with the value range on the left and epochs underneath
+pub fn ascii_plot(curve: &Curve, height: usize) -> String {
+    let mut output = format!("{}\n", curve.title);
+    let (Some(&(first, _)), Some(&(last, _))) = (curve.points.first(), curve.points.last()) else {
+        output.push_str("  no data\n");
+        return output;
+    };
+    let width = ((last - first) as usize + 1).min(MAX_WIDTH);
+    let mut grid = vec![vec![' '; width]; height];
+    let (low, high) = bounds(&curve.points);
+    for &(epoch, value) in &curve.points {
+        let x = if last > first { (epoch - first) as usize * (width - 1) / (last - first) as usize } else { 0 };
+        let y = (scale(value, low, high) * (height - 1) as f64).round() as usize;
+        grid[y][x] = '*';
+    }
+    for (row, cells) in grid.iter().enumerate().rev() {
+        let label = match row {
+            r if r + 1 == height => format!("{:.3}", high),
+            0 => format!("{:.3}", low),
+            _ => String::new(),
+        };
+        writeln!(output, "{:>10} |{}", label, cells.iter().collect::<String>()).unwrap();
+    }
+    writeln!(output, "{:>10} +{}", "", "-".repeat(width)).unwrap();
+    if last > first {
+        writeln!(output, "{:>11}{:>w$}", first, last, w = width - 1).unwrap();
+    } else {
+        writeln!(output, "{:>11}", first).unwrap();
+    }
+    output
+}
+
+/// Every curve as a line chart, one above the other
+pub fn svg(curves: &[Curve]) -> String {
+    const WIDTH: usize = 480;
+    const HEIGHT: usize = 120;
+    const MARGIN: usize = 40;
+    let panel = HEIGHT + 2 * MARGIN;
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="11">"#,
+             WIDTH + 2 * MARGIN, panel * curves.len()).unwrap();
+    for (i, curve) in curves.iter().enumerate() {
+        let top = i * panel + MARGIN;
+        writeln!(svg, r#"<text x="{}" y="{}" font-size="14">{}</text>"#, MARGIN, top - 10, curve.title).unwrap();
+        writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="gray"/>"#, MARGIN, top, WIDTH, HEIGHT).unwrap();
+        let (Some(&(first, _)), Some(&(last, _))) = (curve.points.first(), curve.points.last()) else {
+            continue;
+        };
+        let (low, high) = bounds(&curve.points);
+        let points: Vec<String> = curve.points.iter()
+            .map(|&(epoch, value)| {
+                let x = MARGIN as f64 + if last > first { (epoch - first) as f64 / (last - first) as f64 * WIDTH as f64 } else { 0. };
+                let y = (top + HEIGHT) as f64 - scale(value, low, high) * HEIGHT as f64;
+                format!("{:.1},{:.1}", x, y)
+            })
+            .collect();
+        writeln!(svg, r#"<polyline points="{}" fill="none" stroke="steelblue" stroke-width="2"/>"#, points.join(" ")).unwrap();
+        writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end">{:.3}</text>"#, MARGIN - 4, top + 10, high).unwrap();
+        writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end">{:.3}</text>"#, MARGIN - 4, top + HEIGHT, low).unwrap();
+        writeln!(svg, r#"<text x="{}" y="{}">epoch {}</text>"#, MARGIN, top + HEIGHT + 14, first).unwrap();
+        writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end">epoch {}</text>"#, MARGIN + WIDTH, top + HEIGHT + 14, last).unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// A table of every epoch, how the games ended over the whole run, then a plot of each curve
+pub fn report(metrics: &[EpochMetrics]) -> String {
+    let mut output = String::new();
+    writeln!(output, "{:>5}  {:>20}  {:>14}  {:>6}  {:>7}  {:>8}  {:>6}",
+             "Epoch", "Fitness (min/avg/max)", "W/D/L", "Length", "History", "Champion", "Time").unwrap();
+    for m in metrics {
+        let history = m.history_rate().map_or("-".to_string(), |rate| format!("{:.1}%", 100. * rate));
+        writeln!(output, "{:>5}  {:>6.3} {:>6.3} {:>6.3}  {:>14}  {:>6.1}  {:>7}  {:>8}  {:>5.1}s",
+                 m.epoch, m.worst_fitness(), m.mean_fitness(), m.best_fitness(),
+                 format!("{}/{}/{}", m.results[0], m.results[1], m.results[2]),
+                 m.average_length, history, m.champion, m.seconds).unwrap();
+    }
+    let mut terminations: BTreeMap<&str, u32> = BTreeMap::new();
+    for m in metrics {
+        for (reason, count) in &m.terminations {
+            *terminations.entry(reason).or_default() += count;
+        }
+    }
+    let games: u32 = terminations.values().sum();
+    writeln!(output, "\nHow {} games ended:", games).unwrap();
+    for (reason, count) in &terminations {
+        writeln!(output, "  {:<22} {:>6}  {:>5.1}%", reason, count, 100. * *count as f64 / games as f64).unwrap();
+    }
+    for curve in curves(metrics) {
+        output.push('\n');
+        output.push_str(&ascii_plot(&curve, 8));
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod test {
+    use chess::{ChessMove, Square};
+    use crate::adjudication::Termination;
+    use crate::arena::{GameRecord, Outcome};
+    use crate::metrics::{append, ascii_plot, curves, load, report, svg, Curve, EpochMetrics};
+
+    fn epoch(epoch: i32) -> EpochMetrics {
+        let mut metrics = EpochMetrics::new(epoch);
+        let e4 = ChessMove::new(Square::E2, Square::E4, None);
+        metrics.record(&GameRecord { moves: vec![e4; 10], outcome: Outcome::WhiteWins, termination: Termination::Checkmate });
+        metrics.record(&GameRecord { moves: vec![e4; 20], outcome: Outcome::Draw, termination: Termination::MaxLength });
+        metrics.fitness = vec![0.25, 0.75];
+        metrics.history_score = epoch as f64;
+        metrics.history_games = 2 * epoch as usize;
+        metrics.benchmarks.insert("random".to_string(), 0.75);
+        metrics
+    }
+
+    #[test]
+    fn records_games() {
+        let metrics = epoch(1);
+        assert_eq!(metrics.games, 2);
+        assert_eq!(metrics.results, [1, 1, 0]);
+        assert_eq!(metrics.average_length, 15.);
+        assert_eq!(metrics.terminations["checkmate"], 1);
+        assert_eq!((metrics.worst_fitness(), metrics.mean_fitness(), metrics.best_fitness()), (0.25, 0.5, 0.75));
+        assert_eq!(metrics.history_rate(), Some(0.5));
+        assert_eq!(epoch(0).history_rate(), None);
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let file = std::env::temp_dir().join("chessers_metrics.jsonl");
+        let file = file.to_str().unwrap();
+        let _ = std::fs::remove_file(file);
+        append(file, &epoch(0));
+        append(file, &epoch(1));
+        assert_eq!(load(file), vec![epoch(0), epoch(1)]);
+    }
+
+    #[test]
+    fn plots() {
+        let curve = Curve { title: "Rising".to_string(), points: vec![(0, 0.), (1, 1.), (2, 2.)] };
+        let plot = ascii_plot(&curve, 3);
+        let lines: Vec<&str> = plot.lines().collect();
+        assert_eq!(lines[1], "     2.000 |  *");
+        assert_eq!(lines[2], "           | * ");
+        assert_eq!(lines[3], "     0.000 |*  ");
+
+        let metrics = [epoch(0), epoch(1), epoch(2)];
+        let all = curves(&metrics);
+        assert_eq!(all[2].points.len(), 2);
+        assert_eq!(svg(&all).matches("<polyline").count(), all.len());
+        let text = report(&metrics);
+        assert!(text.contains("How 6 games ended:"));
+        assert!(text.contains("Best fitness"));
+        assert_eq!(all.last().unwrap().title, "Champion score against random");
+        let old = r#"{"epoch":0,"fitness":[],"results":[0,0,0],"games":0,"average_length":0.0,"terminations":{},"seconds":0.0,"champion":0,"history_score":0.0,"history_games":0}"#;
+        assert!(serde_json::from_str::<EpochMetrics>(old).unwrap().benchmarks.is_empty());
+    }
+}
@@ -1,36 +1,56 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use chess::{BitBoard, Board, ChessMove, Color, MoveGen, Piece};
 use candle_core::{Device, DType, Tensor};
 use candle_nn::{Conv2d, ConvTranspose2d, Module, VarBuilder, VarMap};
-use crate::player::Player;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::player::{Action, Player};
 
 pub struct ChessNet {
     varmap: VarMap,
     c1: Conv2d,
     t1: ConvTranspose2d,
+    /// Mutation step size of each tensor by name, inherited through [`ChessNet::merge`]
+    pub(crate) step_sizes: HashMap<String, f64>,
 }
 
 impl  ChessNet {
+    /// The layers built by [`ChessNet::new`]
+    pub const ARCHITECTURE: &'static str = "c1 Conv2d 6->2 3x3, relu, t1 ConvTranspose2d 2->2 3x3, relu";
+
     pub fn new(varmap: VarMap) -> ChessNet {
         let vs = VarBuilder::from_varmap(&varmap, DType::F64, &Device::Cpu);
         ChessNet {
             varmap,
             c1: candle_nn::conv2d(6, 2, 3, Default::default(), vs.pp("c1")).expect(""),
             t1: candle_nn::conv_transpose2d(2, 2, 3, Default::default(), vs.pp("t1")).expect(""),
+            step_sizes: HashMap::new(),
         }
     }
     
     pub fn from_file(safe_tensors_file: &str) -> ChessNet {
-        let mut varmap = VarMap::new();
-        varmap.load(safe_tensors_file).expect("Coulnd't read safetensors file");
-        ChessNet::new(varmap)
+        ChessNet::load(safe_tensors_file).unwrap_or_else(|e| panic!("{}", e))
     }
 
-    pub fn get_weights_and_biases(&self, name: &str) -> (&Tensor, &Tensor) {
-        match name {
-            "c1" => (self.c1.weight(), self.c1.bias().unwrap()),
-            "t1" => (self.t1.weight(), self.t1.bias().unwrap()),
-            _ => panic!("Invalid layer name: {}", name)
-        }
+    /// Read a saved model, failing on a missing, unreadable or mismatched file
+    pub fn load(safe_tensors_file: &str) -> Result<ChessNet, String> {
+        // VarMap::load only fills variables that already exist, so build the layers first
+        let mut net = ChessNet::new(VarMap::new());
+        net.varmap.load(safe_tensors_file)
+            .map_err(|e| format!("Couldn't read model {:?}: {}", safe_tensors_file, e))?;
+        Ok(net)
+    }
+
+    /// Every tensor in the model, sorted by name so two models line up
+    pub fn named_tensors(&self) -> Vec<(String, Tensor)> {
+        let data = self.varmap.data().lock().unwrap();
+        let mut tensors: Vec<(String, Tensor)> = data.iter()
+            .map(|(name, var)| (name.clone(), var.as_tensor().clone()))
+            .collect();
+        tensors.sort_by(|(a, _), (b, _)| a.cmp(b));
+        tensors
     }
 
     pub fn save(&self, file: String) {
@@ -107,28 +127,83 @@ impl  ChessNet {
     }
 }
 
-impl  Player for ChessNet {
-    fn make_move(&self, board: &Board) -> ChessMove {
+impl  ChessNet {
+    /// The 2x8x8 output for a position: how desirable it is to move away
+    /// from, then to, each square
+    fn scores(&self, board: &Board) -> Tensor {
         let x = match ChessNet::board_to_tensor(board) {
             Ok(ok) => ok.unsqueeze(0).unwrap(),
             Err(e) => panic!("{:?}", e)
         };
-        // find desirable board positions
-        let scores = match self.forward(&x) {
+        match self.forward(&x) {
             Ok(s) => s.get(0).unwrap(),
             Err(e) => panic!("{:?}", e)
-        };
-        let moves = MoveGen::new_legal(board);
+        }
+    }
+
+    /// Both output channels indexed by rank then file, as the scores are read by [`ChessNet::ranked_moves`]
+    pub fn heatmaps(&self, board: &Board) -> [[[f64; 8]; 8]; 2] {
+        let scores: Vec<Vec<Vec<f64>>> = self.scores(board).to_vec3().unwrap();
+        let mut maps = [[[0.; 8]; 8]; 2];
+        for (channel, map) in maps.iter_mut().enumerate() {
+            for (rank, row) in map.iter_mut().enumerate() {
+                for (file, value) in row.iter_mut().enumerate() {
+                    *value = scores[channel][file][rank];
+                }
+            }
+        }
+        maps
+    }
+
+    /// Every legal move with its score, best first
+    pub fn ranked_moves(&self, board: &Board) -> Vec<(ChessMove, f64)> {
+        // find desirable board positions
+        let scores = self.scores(board);
         // compare legal moves to desirable position to find which one gets closest
-        let best_move = match moves.max_by(|m, n| {
-            ChessNet::move_to_score(m, &scores)
-                .partial_cmp(&ChessNet::move_to_score(n, &scores)).unwrap()
-        }) {
-            Some(m) => m,
-            None => panic!("Didn't find a best move")
+        let mut ranked: Vec<(ChessMove, f64)> = MoveGen::new_legal(board)
+            .map(|m| (m, ChessNet::move_to_score(&m, &scores)))
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        ranked
+    }
+}
+
+impl  Player for ChessNet {
+    fn make_move(&self, board: &Board) -> Action {
+        match self.ranked_moves(board).first() {
+            Some(&(m, _)) => Action::Move(m),
+            None => Action::Error("Didn't find a best move".to_string()),
+        }
+    }
+}
+
+/// Plays a network's moves with probability softmax(score / temperature), so
+/// low temperatures play almost like the network and high ones almost at random
+pub struct SampledNet {
+    pub net: ChessNet,
+    pub temperature: f64,
+    rng: RefCell<StdRng>,
+}
+
+impl SampledNet {
+    pub fn new(net: ChessNet, temperature: f64, seed: Option<u64>) -> SampledNet {
+        let rng = seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        SampledNet { net, temperature, rng: RefCell::new(rng) }
+    }
+}
+
+impl Player for SampledNet {
+    fn make_move(&self, board: &Board) -> Action {
+        let ranked = self.net.ranked_moves(board);
+        let Some(&(_, best)) = ranked.first() else {
+            return Action::Error("Didn't find a best move".to_string());
         };
-        // println!("{}", best_move);
-        best_move
+        // subtract the best score so the exponentials can't overflow
+        let weights: Vec<f64> = ranked.iter().map(|(_, score)| ((score - best) / self.temperature).exp()).collect();
+        match WeightedIndex::new(&weights) {
+            Ok(index) => Action::Move(ranked[index.sample(&mut *self.rng.borrow_mut())].0),
+            Err(_) => Action::Move(ranked[0].0),
+        }
     }
 }
 
@@ -138,7 +213,8 @@ mod test {
     use candle_core::{Device, Module, Tensor};
     use candle_nn::{VarMap};
     use chess::{Board, Color, Piece};
-    use crate::nn::ChessNet;
+    use crate::nn::{ChessNet, SampledNet};
+    use crate::player::{Action, Player};
 
     #[test]
     fn dims() {
@@ -157,7 +233,51 @@ mod test {
             }
         };
         println!("{:?}", output.shape());
-        println!("{:?}", model.get_weights_and_biases("c1").1.shape())
+        for (name, tensor) in model.named_tensors() {
+            println!("{}: {:?}", name, tensor.shape());
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let model = ChessNet::new(VarMap::new());
+        let file = std::env::temp_dir().join("chessers_save_and_load.safetensors");
+        model.save(file.to_str().unwrap().to_string());
+        let loaded = ChessNet::from_file(file.to_str().unwrap());
+        for ((name, saved), (_, read)) in model.named_tensors().iter().zip(loaded.named_tensors()) {
+            let diff: f64 = (saved - read).unwrap().abs().unwrap().sum_all().unwrap().to_scalar().unwrap();
+            assert_eq!(diff, 0., "{} was not loaded", name);
+        }
+    }
+
+    #[test]
+    fn ranked_moves() {
+        let model = ChessNet::new(VarMap::new());
+        let ranked = model.ranked_moves(&Board::default());
+        assert_eq!(ranked.len(), 20);
+        assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(model.make_move(&Board::default()), Action::Move(ranked[0].0));
+    }
+
+    #[test]
+    fn sampling_temperature() {
+        let file = std::env::temp_dir().join("chessers_sampled.safetensors");
+        let file = file.to_str().unwrap();
+        ChessNet::new(VarMap::new()).save(file.to_string());
+        let board = Board::default();
+        let ranked = ChessNet::from_file(file).ranked_moves(&board);
+        let best = ranked[0].0;
+        // a random net can score several moves alike, and any of those is fine when cold
+        let top: Vec<Action> = ranked.iter().filter(|(_, score)| ranked[0].1 - score < 1e-6).map(|&(m, _)| Action::Move(m)).collect();
+        let cold = SampledNet::new(ChessNet::from_file(file), 1e-9, None);
+        assert!(top.contains(&cold.make_move(&board)));
+
+        let hot = |seed| {
+            let player = SampledNet::new(ChessNet::from_file(file), 100., Some(seed));
+            (0..10).map(|_| player.make_move(&board)).collect::<Vec<Action>>()
+        };
+        assert_eq!(hot(7), hot(7));
+        assert!(hot(7).iter().any(|action| *action != Action::Move(best)));
     }
 
     #[test]
@@ -0,0 +1,106 @@
+use std::str::FromStr;
+use chess::{Board, ChessMove, MoveGen};
+use rand::prelude::*;
+
+/// Well known opening lines in coordinate notation, used to give otherwise
+/// deterministic players a variety of starting positions
+pub const OPENINGS: [&str; 25] = [
+    "",
+    "e2e4 e7e5 g1f3 b8c6 f1b5",
+    "e2e4 e7e5 g1f3 b8c6 f1c4",
+    "e2e4 e7e5 g1f3 g8f6",
+    "e2e4 e7e5 f2f4",
+    "e2e4 e7e5 b1c3",
+    "e2e4 c7c5",
+    "e2e4 c7c5 g1f3 d7d6 d2d4",
+    "e2e4 e7e6 d2d4 d7d5",
+    "e2e4 c7c6 d2d4 d7d5",
+    "e2e4 d7d5 e4d5 d8d5",
+    "e2e4 g8f6",
+    "e2e4 d7d6 d2d4 g8f6 b1c3",
+    "d2d4 d7d5 c2c4",
+    "d2d4 d7d5 c2c4 e7e6",
+    "d2d4 d7d5 c2c4 d5c4",
+    "d2d4 d7d5 c2c4 c7c6",
+    "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4",
+    "d2d4 g8f6 c2c4 e7e6 g1f3 b7b6",
+    "d2d4 g8f6 c2c4 g7g6 b1c3 f8g7",
+    "d2d4 g8f6 c2c4 g7g6 b1c3 d7d5",
+    "d2d4 f7f5",
+    "c2c4 e7e5",
+    "g1f3 d7d5 c2c4",
+    "f2f4 d7d5",
+];
+
+/// Parse a space separated line of coordinate moves
+pub fn parse_line(line: &str) -> Vec<ChessMove> {
+    line.split_whitespace()
+        .map(|m| ChessMove::from_str(m).unwrap_or_else(|_| panic!("Invalid opening move {}", m)))
+        .collect()
+}
+
+/// Pick `count` openings, avoiding repeats until the book runs out
+pub fn sample<R: Rng>(count: usize, rng: &mut R) -> Vec<Vec<ChessMove>> {
+    let mut lines = vec!();
+    while lines.len() < count {
+        let wanted = (count - lines.len()).min(OPENINGS.len());
+        lines.extend(OPENINGS.choose_multiple(rng, wanted).map(|line| parse_line(line)));
+    }
+    lines
+}
+
+/// Extend an opening with `plies` random legal moves, stopping early if the
+/// game ends
+pub fn randomise<R: Rng>(line: &[ChessMove], plies: usize, rng: &mut R) -> Vec<ChessMove> {
+    let mut board = Board::default();
+    for &chess_move in line {
+        board = board.make_move_new(chess_move);
+    }
+    let mut extended = line.to_vec();
+    for _ in 0..plies {
+        match MoveGen::new_legal(&board).choose(rng) {
+            Some(chess_move) => {
+                board = board.make_move_new(chess_move);
+                extended.push(chess_move);
+            },
+            None => break,
+        }
+    }
+    extended
+}
+
+
+#[cfg(test)]
+mod test {
+    use chess::Board;
+    use rand::thread_rng;
+    use crate::openings::{parse_line, randomise, sample, OPENINGS};
+
+    #[test]
+    fn every_opening_is_legal() {
+        for line in OPENINGS {
+            let mut board = Board::default();
+            for chess_move in parse_line(line) {
+                assert!(board.legal(chess_move), "{} in {}", chess_move, line);
+                board = board.make_move_new(chess_move);
+            }
+        }
+    }
+
+    #[test]
+    fn randomised_openings_stay_legal() {
+        let line = randomise(&parse_line(OPENINGS[1]), 4, &mut thread_rng());
+        assert_eq!(line.len(), 9);
+        let mut board = Board::default();
+        for chess_move in line {
+            assert!(board.legal(chess_move));
+            board = board.make_move_new(chess_move);
+        }
+    }
+
+    #[test]
+    fn sample_fills_request() {
+        assert_eq!(sample(3, &mut thread_rng()).len(), 3);
+        assert_eq!(sample(OPENINGS.len() + 5, &mut thread_rng()).len(), OPENINGS.len() + 5);
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::Write;
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece};
+use crate::arena::{GameRecord, Outcome};
+
+/// Standard algebraic notation for a legal move, e.g. `Nbd7`, `exd5`, `e8=Q+`
+pub fn san(board: &Board, chess_move: ChessMove) -> String {
+    let source = chess_move.get_source();
+    let dest = chess_move.get_dest();
+    let piece = board.piece_on(source).expect("No piece on the source square");
+    let capture = board.piece_on(dest).is_some();
+    let mut text = String::new();
+
+    if piece == Piece::King && source.get_file().to_index().abs_diff(dest.get_file().to_index()) == 2 {
+        text.push_str(if dest.get_file().to_index() > source.get_file().to_index() { "O-O" } else { "O-O-O" });
+    } else if piece == Piece::Pawn {
+        // a pawn changing file always captures, even en passant onto an empty square
+        if source.get_file() != dest.get_file() {
+            text.push(file_char(source));
+            text.push('x');
+        }
+        text.push_str(&dest.to_string());
+        if let Some(promotion) = chess_move.get_promotion() {
+            text.push('=');
+            text.push_str(&promotion.to_string(Color::White));
+        }
+    } else {
+        text.push_str(&piece.to_string(Color::White));
+        // other pieces of the same kind that could also reach the destination
+        let rivals: Vec<ChessMove> = MoveGen::new_legal(board)
+            .filter(|m| m.get_dest() == dest && m.get_source() != source && board.piece_on(m.get_source()) == Some(piece))
+            .collect();
+        if !rivals.is_empty() {
+            let same_file = rivals.iter().any(|m| m.get_source().get_file() == source.get_file());
+            let same_rank = rivals.iter().any(|m| m.get_source().get_rank() == source.get_rank());
+            if !same_file {
+                text.push(file_char(source));
+            } else if !same_rank {
+                text.push(rank_char(source));
+            } else {
+                text.push_str(&source.to_string());
+            }
+        }
+        if capture {
+            text.push('x');
+        }
+        text.push_str(&dest.to_string());
+    }
+
+    let after = board.make_move_new(chess_move);
+    if after.status() == BoardStatus::Checkmate {
+        text.push('#');
+    } else if after.checkers().popcnt() > 0 {
+        text.push('+');
+    }
+    text
+}
+
+fn file_char(square: chess::Square) -> char {
+    (b'a' + square.get_file().to_index() as u8) as char
+}
+
+fn rank_char(square: chess::Square) -> char {
+    (b'1' + square.get_rank().to_index() as u8) as char
+}
+
+/// PGN result token
+pub fn result(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::WhiteWins => "1-0",
+        Outcome::Draw => "1/2-1/2",
+        Outcome::BlackWins => "0-1",
+    }
+}
+
+/// Numbered move text from the starting position, e.g. `1. e4 e5 2. Nf3`
+pub fn movetext(moves: &[ChessMove]) -> String {
+    let mut board = Board::default();
+    let mut parts = vec!();
+    for (ply, &chess_move) in moves.iter().enumerate() {
+        if ply % 2 == 0 {
+            parts.push(format!("{}.", ply / 2 + 1));
+        }
+        parts.push(san(&board, chess_move));
+        board = board.make_move_new(chess_move);
+    }
+    parts.join(" ")
+}
+
+/// Write one game with the seven tag roster filled from `tags`, then the
+/// standard Termination tag, with the detailed reason as a comment before the result
+pub fn write_game<W: Write>(out: &mut W, tags: &[(&str, String)], game: &GameRecord) -> std::io::Result<()> {
+    for (name, value) in tags {
+        writeln!(out, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+    }
+    writeln!(out, "[Result \"{}\"]", result(game.outcome))?;
+    writeln!(out, "[Termination \"{}\"]", game.termination.pgn_tag())?;
+    writeln!(out)?;
+    // PGN lines should stay under 80 characters
+    let mut line = String::new();
+    let reason = format!("{{{}}}", game.termination);
+    let tokens = movetext(&game.moves);
+    for token in tokens.split(' ').filter(|t| !t.is_empty()).chain(reason.split(' ')).chain([result(game.outcome)]) {
+        if !line.is_empty() && line.len() + token.len() >= 80 {
+            writeln!(out, "{}", line)?;
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token);
+    }
+    writeln!(out, "{}", line)?;
+    writeln!(out)
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use chess::{Board, ChessMove};
+    use crate::adjudication::Termination;
+    use crate::arena::{GameRecord, Outcome};
+    use crate::openings::parse_line;
+    use crate::pgn::{movetext, san, write_game};
+
+    fn san_of(fen: &str, uci: &str) -> String {
+        san(&Board::from_str(fen).unwrap(), ChessMove::from_str(uci).unwrap())
+    }
+
+    #[test]
+    fn opening_moves() {
+        assert_eq!(movetext(&parse_line("e2e4 e7e5 g1f3 b8c6 f1b5")), "1. e4 e5 2. Nf3 Nc6 3. Bb5");
+        assert_eq!(movetext(&parse_line("e2e4 d7d5 e4d5 d8d5")), "1. e4 d5 2. exd5 Qxd5");
+    }
+
+    #[test]
+    fn special_moves() {
+        assert_eq!(san_of("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"), "O-O");
+        assert_eq!(san_of("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"), "O-O-O");
+        assert_eq!(san_of("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), "b8=Q+");
+        assert_eq!(san_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+        assert_eq!(san_of("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn disambiguation() {
+        assert_eq!(san_of("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1"), "Rad1");
+        assert_eq!(san_of("4k3/8/R7/8/8/8/R7/4K3 w - - 0 1", "a2a4"), "R2a4");
+        assert_eq!(san_of("7k/2N5/8/8/8/2N1N3/8/4K3 w - - 0 1", "c3d5"), "Nc3d5");
+    }
+
+    #[test]
+    fn standard_termination_tag() {
+        let game = GameRecord { moves: vec!(), outcome: Outcome::Draw, termination: Termination::MaxLength };
+        let mut out = vec!();
+        write_game(&mut out, &[], &game).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("[Termination \"adjudication\"]"));
+        assert!(text.ends_with("{maximum length} 1/2-1/2\n\n"));
+    }
+
+    #[test]
+    fn writes_tags_and_result() {
+        let game = GameRecord {
+            moves: parse_line("f2f3 e7e5 g2g4 d8h4"),
+            outcome: Outcome::BlackWins,
+            termination: Termination::Checkmate,
+        };
+        let mut out = vec!();
+        write_game(&mut out, &[("White", "a".to_string())], &game).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("[White \"a\"]"));
+        assert!(text.contains("[Result \"0-1\"]"));
+        assert!(text.contains("[Termination \"normal\"]"));
+        assert!(text.contains("1. f3 e5 2. g4 Qh4# {checkmate} 0-1"));
+    }
+}
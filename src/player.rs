@@ -1,75 +1,447 @@
-use chess::{Board, ChessMove, MoveGen};
-use rand::prelude::IteratorRandom;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::arena::material_balance;
+use crate::nn::ChessNet;
+use crate::pgn::san;
+use crate::ui::ConsoleUI;
+use crate::time_manager::{SearchLimits, TimeManager};
+
+/// What a player does on its turn
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Move(ChessMove),
+    Resign,
+    /// Offer a draw, then move as usual. The offer stands until the opponent moves.
+    OfferDraw,
+    /// Take the opponent's standing draw offer
+    AcceptDraw,
+    /// Take back this player's last move and the reply to it
+    TakeBack,
+    /// The player can't carry on, e.g. its input was closed
+    Error(String),
+}
 
 pub trait Player {
-    fn make_move(&self, board: &Board) -> ChessMove;
+    fn make_move(&self, board: &Board) -> Action;
+
+    /// Move within search limits, which carry the clock when there is one.
+    /// Players that don't think can leave this alone and ignore the limits;
+    /// players that do should budget with a [`TimeManager`](crate::time_manager::TimeManager).
+    fn make_limited_move(&self, board: &Board, _limits: &SearchLimits) -> Action {
+        self.make_move(board)
+    }
+}
+
+/// Wraps a bot so it resigns once it is `margin` pawns of material behind
+pub struct ResigningPlayer {
+    pub player: Box<dyn Player>,
+    pub margin: i32,
+}
+
+impl ResigningPlayer {
+    fn hopeless(&self, board: &Board) -> bool {
+        let lead = match board.side_to_move() {
+            Color::White => material_balance(board),
+            Color::Black => -material_balance(board),
+        };
+        lead <= -self.margin
+    }
+}
+
+impl Player for ResigningPlayer {
+    fn make_move(&self, board: &Board) -> Action {
+        if self.hopeless(board) { Action::Resign } else { self.player.make_move(board) }
+    }
+
+    fn make_limited_move(&self, board: &Board, limits: &SearchLimits) -> Action {
+        if self.hopeless(board) { Action::Resign } else { self.player.make_limited_move(board, limits) }
+    }
 }
 
+/// Moves at random, replaying the same game every time when given a seed
 pub struct RandomPlayer {
+    rng: RefCell<StdRng>,
+}
 
+impl RandomPlayer {
+    pub fn new(seed: Option<u64>) -> RandomPlayer {
+        let rng = seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        RandomPlayer { rng: RefCell::new(rng) }
+    }
+}
+
+impl Default for RandomPlayer {
+    fn default() -> Self {
+        RandomPlayer::new(None)
+    }
 }
 
 impl Player for RandomPlayer {
-    fn make_move(&self, board: &Board) -> ChessMove {
+    fn make_move(&self, board: &Board) -> Action {
         let moves = MoveGen::new_legal(board);
-        let random_move = moves.choose(&mut rand::thread_rng()).expect("There should be a legal move");
-        println!("{}", random_move);
-        random_move
+        match moves.choose(&mut *self.rng.borrow_mut()) {
+            Some(random_move) => Action::Move(random_move),
+            None => Action::Error("no legal moves".to_string()),
+        }
     }
 }
 
-pub struct HumanPlayer {
+/// Score for delivering mate, less the plies it takes
+const MATE: i32 = 100_000;
+
+/// Usual 1/3/3/5/9 piece values, with the king priceless since it can't be taken
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight | Piece::Bishop => 3,
+        Piece::Rook => 5,
+        Piece::Queen => 9,
+        Piece::King => 0,
+    }
+}
+
+/// Value of whatever a move takes, if anything, counting en passant as a pawn
+fn captured(board: &Board, chess_move: ChessMove) -> Option<i32> {
+    match board.piece_on(chess_move.get_dest()) {
+        Some(piece) => Some(piece_value(piece)),
+        None if board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+            && chess_move.get_source().get_file() != chess_move.get_dest().get_file() => Some(1),
+        None => None,
+    }
+}
+
+/// Material lead of the side to move, or a mate score once the game is over
+fn evaluate(board: &Board, ply: i32) -> i32 {
+    match board.status() {
+        BoardStatus::Checkmate => ply - MATE,
+        BoardStatus::Stalemate => 0,
+        BoardStatus::Ongoing => match board.side_to_move() {
+            chess::Color::White => material_balance(board),
+            chess::Color::Black => -material_balance(board),
+        },
+    }
+}
+
+/// Pick one of the highest scoring moves at random
+fn best_by<F: Fn(ChessMove) -> i32>(board: &Board, score: F) -> Action {
+    let scored: Vec<(ChessMove, i32)> = MoveGen::new_legal(board).map(|m| (m, score(m))).collect();
+    let Some(best) = scored.iter().map(|&(_, s)| s).max() else {
+        return Action::Error("no legal moves".to_string());
+    };
+    let choices: Vec<ChessMove> = scored.into_iter().filter(|&(_, s)| s == best).map(|(m, _)| m).collect();
+    Action::Move(*choices.choose(&mut thread_rng()).expect("There is a best move"))
+}
+
+/// Takes the most valuable piece it can, otherwise moves at random
+pub struct GreedyPlayer;
+
+impl Player for GreedyPlayer {
+    fn make_move(&self, board: &Board) -> Action {
+        best_by(board, |m| captured(board, m).unwrap_or(-1))
+    }
+}
+
+/// Plays whichever move leaves it furthest ahead on material, looking no further
+pub struct MaterialPlayer;
+
+impl Player for MaterialPlayer {
+    fn make_move(&self, board: &Board) -> Action {
+        best_by(board, |m| -evaluate(&board.make_move_new(m), 1))
+    }
+}
+
+/// Alpha-beta search on material to a fixed depth, deepening one ply at a
+/// time so a clock or node limit can cut it short
+pub struct MinimaxPlayer {
+    pub depth: u32,
+}
+
+/// State shared by one search
+struct Search {
+    manager: TimeManager,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl Search {
+    /// Negamax score of `board` for the side to move, or None if the search was stopped
+    fn negamax(&mut self, board: &Board, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> Option<i32> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.manager.must_stop(self.nodes) {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return None;
+        }
+        if depth == 0 || board.status() != BoardStatus::Ongoing {
+            return Some(evaluate(board, ply));
+        }
+        for chess_move in ordered(board, None) {
+            let score = -self.negamax(&board.make_move_new(chess_move), depth - 1, ply + 1, -beta, -alpha)?;
+            if score >= beta {
+                return Some(beta);
+            }
+            alpha = alpha.max(score);
+        }
+        Some(alpha)
+    }
+
+    /// Best move and its score at one depth
+    fn root(&mut self, board: &Board, depth: u32, previous: Option<ChessMove>) -> Option<(ChessMove, i32)> {
+        let mut best = None;
+        let mut alpha = -MATE - 1;
+        for chess_move in ordered(board, previous) {
+            let score = -self.negamax(&board.make_move_new(chess_move), depth - 1, 1, -MATE - 1, -alpha)?;
+            if best.is_none() || score > alpha {
+                alpha = score;
+                best = Some((chess_move, score));
+            }
+        }
+        best
+    }
+}
+
+/// Legal moves with the previous best first, then captures of the most valuable pieces
+fn ordered(board: &Board, first: Option<ChessMove>) -> Vec<ChessMove> {
+    let mut moves: Vec<ChessMove> = MoveGen::new_legal(board).collect();
+    moves.sort_by_key(|&m| (Some(m) != first, -captured(board, m).unwrap_or(-1)));
+    moves
+}
+
+impl Player for MinimaxPlayer {
+    fn make_move(&self, board: &Board) -> Action {
+        self.make_limited_move(board, &SearchLimits::default())
+    }
 
+    /// Searches to the player's own depth unless the limits say otherwise
+    fn make_limited_move(&self, board: &Board, limits: &SearchLimits) -> Action {
+        let mut manager = TimeManager::new(limits, board);
+        if manager.is_unlimited() {
+            manager = TimeManager::new(&SearchLimits { depth: Some(self.depth.max(1)), ..*limits }, board);
+        }
+        let mut search = Search { manager, nodes: 0, aborted: false };
+        let mut best = None;
+        for depth in 1.. {
+            match search.root(board, depth, best) {
+                Some((chess_move, _)) => best = Some(chess_move),
+                None => break,
+            }
+            if !search.manager.next_iteration(depth, best.expect("Just searched"), search.nodes) {
+                break;
+            }
+        }
+        match best.or_else(|| MoveGen::new_legal(board).next()) {
+            Some(chess_move) => Action::Move(chess_move),
+            None => Action::Error("no legal moves".to_string()),
+        }
+    }
+}
+
+const HELP: &str = "Enter a move as SAN (Nf3, exd5, O-O) or coordinates (g1f3, e7e8q), or one of:
+  list    every legal move
+  undo    take back your last move and your opponent's reply
+  hint    ask the hint model for a move
+  flip    turn the board around
+  fen     print the position as FEN
+  draw    offer a draw, then make your move
+  accept  accept your opponent's draw offer
+  resign  give up the game
+  help    show this listing";
+
+/// A legal move in coordinates (e2e4, e7e8q) or SAN (e4, Nf3, O-O)
+pub fn parse_move(board: &Board, text: &str) -> Option<ChessMove> {
+    ChessMove::from_str(text).ok()
+        .filter(|&coordinates| board.legal(coordinates))
+        .or_else(|| ChessMove::from_san(board, text).ok())
+}
+
+#[derive(Default)]
+pub struct HumanPlayer {
+    /// Model asked for a move by the `hint` command
+    pub hint: Option<ChessNet>,
+    /// Board the `flip` command turns around
+    pub ui: Rc<ConsoleUI>,
 }
 
 impl HumanPlayer {
-    fn get_input(&self, board: &Board) -> Option<ChessMove> {
+    fn get_input(&self, board: &Board) -> Option<Action> {
         let mut user_input = String::new();
-        std::io::stdin()
-            .read_line(&mut user_input)
-            .expect("Failed to read move");
-        if user_input.contains("list") {
-            println!("Here are all the moves you can do:");
-            for legal_move in MoveGen::new_legal(board) {
-                println!("{}", legal_move);
-            };
-            None
+        match std::io::stdin().read_line(&mut user_input) {
+            Ok(0) => Some(Action::Error("input closed".to_string())),
+            Err(e) => Some(Action::Error(e.to_string())),
+            Ok(_) => self.parse_input(board, &user_input),
         }
-        else {
-            match ChessMove::from_san(board, &*user_input) {
-                Ok(user_move) => Some(user_move),
-                _ => {
-                    println!("Invalid move");
+    }
+
+    /// Act on one line of input, or None when the player still has to choose
+    fn parse_input(&self, board: &Board, user_input: &str) -> Option<Action> {
+        match user_input.trim() {
+            "list" => {
+                println!("Here are all the moves you can do:");
+                for legal_move in MoveGen::new_legal(board) {
+                    println!("{}", legal_move);
+                };
+                None
+            },
+            "help" => {
+                println!("{}", HELP);
+                None
+            },
+            "hint" => {
+                match &self.hint {
+                    Some(model) => match model.make_move(board) {
+                        Action::Move(hint) => println!("Try {}", san(board, hint)),
+                        _ => println!("The hint model has no suggestion"),
+                    },
+                    None => println!("No hint model, pass one with --hint"),
+                }
+                None
+            },
+            "flip" => {
+                self.ui.flip(board);
+                None
+            },
+            "fen" => {
+                println!("{}", board);
+                None
+            },
+            "undo" => Some(Action::TakeBack),
+            "resign" => Some(Action::Resign),
+            "draw" => Some(Action::OfferDraw),
+            "accept" => Some(Action::AcceptDraw),
+            text => match parse_move(board, text) {
+                Some(user_move) => Some(Action::Move(user_move)),
+                None => {
+                    println!("Invalid move, type help for the commands");
                     None
                 }
-            }
+            },
         }
     }
 }
 
 impl Player for HumanPlayer {
-    fn make_move(&self, board: &Board) -> ChessMove {
-        let mut user_move = self.get_input(board);
-        while user_move.is_none() {
-            user_move = self.get_input(board);
+    fn make_move(&self, board: &Board) -> Action {
+        let mut action = self.get_input(board);
+        while action.is_none() {
+            action = self.get_input(board);
         }
-        user_move.expect("How did we get out of the while loop?")
+        action.expect("How did we get out of the while loop?")
     }
 }
 
 #[cfg(test)]
 mod test {
-    use chess::{Board, ChessMove, MoveGen};
-    use crate::player::Player;
-    use crate::player::RandomPlayer;
+    use std::str::FromStr;
+    use chess::{Board, ChessMove, MoveGen, Piece, Square};
+    use std::time::Duration;
+    use crate::player::{Action, HumanPlayer, Player, ResigningPlayer};
+    use crate::player::{GreedyPlayer, MaterialPlayer, MinimaxPlayer, RandomPlayer};
+    use crate::time_manager::SearchLimits;
 
     #[test]
     fn get_legal_move() {
-        let p1 = RandomPlayer {};
+        let p1 = RandomPlayer::default();
         let board = Board::default();
         let moves = MoveGen::new_legal(&board);
-        let p1_move = p1.make_move(&board);
+        let Action::Move(p1_move) = p1.make_move(&board) else { panic!("RandomPlayer didn't move") };
         let moves_vec: Vec<ChessMove> = moves.collect();
         assert!(moves_vec.contains(&p1_move));
     }
+
+    #[test]
+    fn seeded_random_repeats() {
+        let play = |seed| {
+            let player = RandomPlayer::new(Some(seed));
+            let mut board = Board::default();
+            let mut moves = Vec::new();
+            for _ in 0..20 {
+                let Action::Move(chess_move) = player.make_move(&board) else { break };
+                moves.push(chess_move);
+                board = board.make_move_new(chess_move);
+            }
+            moves
+        };
+        assert_eq!(play(42), play(42));
+        assert_ne!(play(42), play(43));
+    }
+
+    #[test]
+    fn no_legal_moves_is_an_error() {
+        let mated = Board::from_str("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(matches!(RandomPlayer::default().make_move(&mated), Action::Error(_)));
+    }
+
+    #[test]
+    fn resigns_hopeless_positions() {
+        let player = ResigningPlayer { player: Box::new(RandomPlayer::default()), margin: 9 };
+        let down_a_queen = Board::from_str("3qk3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(player.make_move(&down_a_queen), Action::Resign);
+        let up_a_queen = Board::from_str("3qk3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(matches!(player.make_move(&up_a_queen), Action::Move(_)));
+    }
+
+    #[test]
+    fn human_notation() {
+        let human = HumanPlayer::default();
+        let board = Board::default();
+        let e4 = Action::Move(ChessMove::new(Square::E2, Square::E4, None));
+        assert_eq!(human.parse_input(&board, "e2e4\n"), Some(e4.clone()));
+        assert_eq!(human.parse_input(&board, "e4\n"), Some(e4));
+        assert_eq!(human.parse_input(&board, "e2e5\n"), None);
+        assert_eq!(human.parse_input(&board, "undo\n"), Some(Action::TakeBack));
+        assert_eq!(human.parse_input(&board, "fen\n"), None);
+
+        let promotion = Board::from_str("7k/4P3/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let queen = ChessMove::new(Square::E7, Square::E8, Some(Piece::Queen));
+        assert_eq!(human.parse_input(&promotion, "e7e8q"), Some(Action::Move(queen)));
+    }
+
+    #[test]
+    fn flip_turns_the_board() {
+        let human = HumanPlayer::default();
+        assert_eq!(human.parse_input(&Board::default(), "flip"), None);
+        assert!(human.ui.flipped.get());
+    }
+
+    fn play(player: &dyn Player, fen: &str) -> ChessMove {
+        match player.make_move(&Board::from_str(fen).unwrap()) {
+            Action::Move(chess_move) => chess_move,
+            other => panic!("Expected a move, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn greedy_takes_the_queen() {
+        // the rook can take a knight or the queen
+        let fen = "4k3/8/8/3n4/8/8/3R2q1/4K3 w - - 0 1";
+        assert_eq!(play(&GreedyPlayer, fen), ChessMove::new(Square::D2, Square::G2, None));
+        assert_eq!(play(&MaterialPlayer, fen), ChessMove::new(Square::D2, Square::G2, None));
+    }
+
+    #[test]
+    fn material_avoids_the_poisoned_pawn() {
+        // taking on d5 wins a pawn but the queen is lost to the c6 pawn; material only looks one ply
+        let fen = "4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1";
+        let grab = ChessMove::new(Square::D1, Square::D5, None);
+        assert_eq!(play(&GreedyPlayer, fen), grab);
+        assert_eq!(play(&MaterialPlayer, fen), grab);
+        assert_ne!(play(&MinimaxPlayer { depth: 2 }, fen), grab);
+    }
+
+    #[test]
+    fn minimax_finds_mate() {
+        // back rank mate in one
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        assert_eq!(play(&MinimaxPlayer { depth: 3 }, fen), ChessMove::new(Square::A1, Square::A8, None));
+        let limits = SearchLimits { movetime: Some(Duration::from_millis(50)), ..Default::default() };
+        let board = Board::from_str(fen).unwrap();
+        let start = std::time::Instant::now();
+        assert!(matches!(MinimaxPlayer { depth: 1 }.make_limited_move(&board, &limits), Action::Move(_)));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
 }
@@ -0,0 +1,192 @@
+use std::collections::BTreeSet;
+use rand::prelude::*;
+use crate::adjudication::Adjudication;
+use crate::arena::play_from;
+use crate::clock::TimeControl;
+use crate::metrics::{ascii_plot, Curve};
+use crate::nn::ChessNet;
+use crate::openings;
+use crate::rating::{player_id, RatingsDb, Record};
+
+/// Iterations of the rating fit, plenty for a few hundred champions
+const FIT_ITERATIONS: usize = 1000;
+
+/// Every epoch champion of a training run played against the others
+pub struct Progress {
+    /// Epoch and file of each champion, oldest first
+    pub champions: Vec<(i32, String)>,
+    /// `records[i][j]` is champion `i`'s record against champion `j`
+    pub records: Vec<Vec<Record>>,
+}
+
+impl Progress {
+    /// The champions listed in a run's champs.csv
+    pub fn champions(run_dir: &str) -> Vec<(i32, String)> {
+        let champs_file = format!("{}/champs.csv", run_dir.trim_end_matches('/'));
+        let mut reader = csv::Reader::from_path(&champs_file)
+            .unwrap_or_else(|e| panic!("Couldn't read {}: {}", champs_file, e));
+        reader.records()
+            .map(|row| {
+                let row = row.unwrap_or_else(|e| panic!("Bad row in {}: {}", champs_file, e));
+                let epoch: i32 = row[0].parse().expect("Epoch isn't a number");
+                let index: usize = row[1].parse().expect("Champion index isn't a number");
+                (epoch, format!("{}/{:04}_{:04}.safetensors", run_dir.trim_end_matches('/'), epoch, index))
+            })
+            .collect()
+    }
+
+    /// Play `games` games between every pair of champions, or with `sample`
+    /// only between each champion and that many others picked at random.
+    /// Like a gauntlet, games come in pairs sharing an opening with colours swapped.
+    pub fn run(champions: Vec<(i32, String)>, games: usize, sample: Option<usize>, adjudication: &Adjudication,
+               time_control: Option<TimeControl>, ratings: &mut RatingsDb) -> Progress {
+        let mut rng = thread_rng();
+        let models: Vec<ChessNet> = champions.iter().map(|(_, file)| ChessNet::from_file(file)).collect();
+        let ids: Vec<String> = champions.iter().map(|(_, file)| player_id(file)).collect();
+        let mut pairs = BTreeSet::new();
+        for i in 0..models.len() {
+            let others: Vec<usize> = (0..models.len()).filter(|&j| j != i).collect();
+            let chosen = match sample {
+                Some(count) => others.choose_multiple(&mut rng, count).cloned().collect(),
+                None => others,
+            };
+            pairs.extend(chosen.into_iter().map(|j| (i.min(j), i.max(j))));
+        }
+
+        let mut records = vec![vec![Record::default(); models.len()]; models.len()];
+        for (i, j) in pairs {
+            let lines = openings::sample(games.div_ceil(2), &mut rng);
+            for game in 0..games {
+                let (white, black) = if game % 2 == 0 { (i, j) } else { (j, i) };
+                let white_score = play_from(&models[white], &models[black], &lines[game / 2], adjudication, time_control)
+                    .outcome.white_score();
+                ratings.record(&ids[white], &ids[black], white_score);
+                records[white][black].add(white_score);
+                records[black][white].add(1. - white_score);
+            }
+        }
+        ratings.commit();
+        Progress { champions, records }
+    }
+
+    /// Bradley-Terry ratings fitted to every game, in Elo with the first
+    /// champion at 0. Each pairing also counts one virtual draw, so a
+    /// perfect score still gives a finite rating.
+    pub fn ratings(&self) -> Vec<f64> {
+        let n = self.champions.len();
+        let played = |i: usize, j: usize| {
+            let record = &self.records[i][j];
+            (record.games() > 0).then(|| (record.wins as f64 + 0.5 * record.draws as f64 + 0.5, record.games() as f64 + 1.))
+        };
+        let mut strength = vec![1.; n];
+        for _ in 0..FIT_ITERATIONS {
+            strength = (0..n).map(|i| {
+                let (points, expected) = (0..n).filter_map(|j| played(i, j).map(|(points, games)| (j, points, games)))
+                    .fold((0., 0.), |(total, expected), (j, points, games)| {
+                        (total + points, expected + games / (strength[i] + strength[j]))
+                    });
+                if expected > 0. { points / expected } else { strength[i] }
+            }).collect();
+        }
+        strength.iter().map(|s| 400. * (s / strength[0]).log10()).collect()
+    }
+
+    /// Least squares slope of the fitted ratings, in Elo per epoch
+    pub fn trend(&self) -> f64 {
+        let ratings = self.ratings();
+        let epochs: Vec<f64> = self.champions.iter().map(|(epoch, _)| *epoch as f64).collect();
+        let n = epochs.len() as f64;
+        let (mean_x, mean_y) = (epochs.iter().sum::<f64>() / n, ratings.iter().sum::<f64>() / n);
+        let covariance: f64 = epochs.iter().zip(&ratings).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let variance: f64 = epochs.iter().map(|x| (x - mean_x).powi(2)).sum();
+        if variance > 0. { covariance / variance } else { 0. }
+    }
+
+    pub fn curve(&self) -> Curve {
+        Curve {
+            title: "Fitted rating (Elo)".to_string(),
+            points: self.champions.iter().map(|(epoch, _)| *epoch).zip(self.ratings()).collect(),
+        }
+    }
+
+    /// The score matrix, row champion against column champion, then the fitted ratings
+    pub fn print(&self) {
+        print!("{:>6}", "Epoch");
+        for (epoch, _) in &self.champions {
+            print!("{:>6}", epoch);
+        }
+        println!("{:>8}", "Total");
+        for (i, (epoch, _)) in self.champions.iter().enumerate() {
+            print!("{:>6}", epoch);
+            let mut total = Record::default();
+            for record in &self.records[i] {
+                match record.games() {
+                    0 => print!("{:>6}", "-"),
+                    _ => print!("{:>5.0}%", 100. * record.score()),
+                }
+                total += *record;
+            }
+            match total.games() {
+                0 => println!("{:>8}", "-"),
+                _ => println!("{:>7.1}%", 100. * total.score()),
+            }
+        }
+        println!();
+        print!("{}", ascii_plot(&self.curve(), 10));
+        println!("Trend: {:+.1} Elo per epoch", self.trend());
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::fs::create_dir_all;
+    use candle_nn::VarMap;
+    use crate::adjudication::Adjudication;
+    use crate::nn::ChessNet;
+    use crate::progress::Progress;
+    use crate::rating::{RatingsDb, Record};
+
+    fn record(wins: u32, draws: u32, losses: u32) -> Record {
+        Record { wins, draws, losses }
+    }
+
+    #[test]
+    fn fitted_ratings_follow_results() {
+        let champions = (0..3).map(|epoch| (epoch, String::new())).collect();
+        let mut progress = Progress { champions, records: vec![vec![Record::default(); 3]; 3] };
+        // each champion beats the one before it three games to one
+        for (i, j) in [(1, 0), (2, 1)] {
+            progress.records[i][j] = record(3, 0, 1);
+            progress.records[j][i] = record(1, 0, 3);
+        }
+        let ratings = progress.ratings();
+        assert_eq!(ratings[0], 0.);
+        assert!(ratings[1] > 0. && ratings[2] > ratings[1]);
+        assert!((ratings[2] - 2. * ratings[1]).abs() < 1.);
+        assert!(progress.trend() > 0.);
+    }
+
+    #[test]
+    fn plays_every_pair() {
+        let dir = std::env::temp_dir().join("chessers_progress_test");
+        create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap();
+        let mut writer = csv::Writer::from_path(format!("{}/champs.csv", dir)).unwrap();
+        writer.write_record(["Epoch", "Champ index", "Score"]).unwrap();
+        for (epoch, index) in [(0, 1), (1, 0), (2, 1)] {
+            ChessNet::new(VarMap::new()).save(format!("{}/{:04}_{:04}.safetensors", dir, epoch, index));
+            writer.write_record([epoch.to_string(), index.to_string(), "0".to_string()]).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let champions = Progress::champions(dir);
+        assert_eq!(champions[2], (2, format!("{}/0002_0001.safetensors", dir)));
+        let mut ratings = RatingsDb::open(format!("{}/ratings.csv", dir));
+        let progress = Progress::run(champions, 2, None, &Adjudication::default(), None, &mut ratings);
+        assert!(progress.records.iter().enumerate().all(|(i, row)| row[i].games() == 0));
+        assert_eq!(progress.records[0][2].games(), 2);
+        assert_eq!(progress.records[0].iter().map(Record::games).sum::<u32>(), 4);
+        progress.print();
+    }
+}
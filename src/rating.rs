@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fs::{create_dir_all, rename};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::arena::resources_dir;
+
+/// Conversion between the Elo-like display scale and the Glicko-2 scale
+const SCALE: f64 = 173.7178;
+/// Constrains how fast volatility can change
+const TAU: f64 = 0.5;
+/// Saves made by this process so far
+static SAVES: AtomicUsize = AtomicUsize::new(0);
+
+/// Glicko-2 rating of a single player, stored on the Elo-like display scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating { rating: 1500., deviation: 350., volatility: 0.06, games: 0 }
+    }
+}
+
+impl Rating {
+    /// 95% confidence interval for the true rating
+    pub fn interval(&self) -> (f64, f64) {
+        (self.rating - 1.96 * self.deviation, self.rating + 1.96 * self.deviation)
+    }
+
+    /// Apply one Glicko-2 rating period, given each opponent's rating before
+    /// the period and the score against them (1 win, 0.5 draw, 0 loss)
+    pub fn update(&self, results: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - 1500.) / SCALE;
+        let phi = self.deviation / SCALE;
+        if results.is_empty() {
+            let phi = (phi * phi + self.volatility * self.volatility).sqrt();
+            return Rating { deviation: phi * SCALE, ..*self };
+        }
+
+        let g = |phi: f64| 1. / (1. + 3. * phi * phi / (PI * PI)).sqrt();
+        let mut v_inverse = 0.;
+        let mut improvement = 0.;
+        for (opponent, score) in results {
+            let mu_j = (opponent.rating - 1500.) / SCALE;
+            let g_j = g(opponent.deviation / SCALE);
+            let expected = 1. / (1. + (-g_j * (mu - mu_j)).exp());
+            v_inverse += g_j * g_j * expected * (1. - expected);
+            improvement += g_j * (score - expected);
+        }
+        let v = 1. / v_inverse;
+        let delta = v * improvement;
+
+        // new volatility by the Illinois algorithm from Glickman's paper
+        let a = (self.volatility * self.volatility).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let d = phi * phi + v + ex;
+            ex * (delta * delta - d) / (2. * d * d) - (x - a) / (TAU * TAU)
+        };
+        let mut big_a = a;
+        let mut big_b = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.;
+            while f(a - k * TAU) < 0. {
+                k += 1.;
+            }
+            a - k * TAU
+        };
+        let mut f_a = f(big_a);
+        let mut f_b = f(big_b);
+        while (big_b - big_a).abs() > 1e-6 {
+            let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+            let f_c = f(big_c);
+            if f_c * f_b <= 0. {
+                big_a = big_b;
+                f_a = f_b;
+            } else {
+                f_a /= 2.;
+            }
+            big_b = big_c;
+            f_b = f_c;
+        }
+        let volatility = (big_a / 2.).exp();
+
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let phi = 1. / (1. / (phi_star * phi_star) + 1. / v).sqrt();
+        let mu = mu + phi * phi * improvement;
+        Rating {
+            rating: mu * SCALE + 1500.,
+            deviation: phi * SCALE,
+            volatility,
+            games: self.games + results.len() as u32,
+        }
+    }
+}
+
+/// Wins, draws and losses from one player's point of view
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Record {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Record {
+    /// Count one game given the points scored in it
+    pub fn add(&mut self, score: f64) {
+        match score {
+            s if s > 0.5 => self.wins += 1,
+            s if s < 0.5 => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Mean points per game, even before any games are played
+    pub fn score(&self) -> f64 {
+        match self.games() {
+            0 => 0.5,
+            games => (self.wins as f64 + 0.5 * self.draws as f64) / games as f64,
+        }
+    }
+
+    /// Elo difference implied by the score, kept finite for clean sweeps
+    pub fn elo(&self) -> f64 {
+        elo_difference(self.score().clamp(SCORE_BOUND, 1. - SCORE_BOUND))
+    }
+
+    /// Variance of a single game's points. Half a win and half a loss are
+    /// added as a prior, so a run of identical results still has some spread
+    /// rather than a certain ±0.
+    pub fn variance(&self) -> f64 {
+        let (wins, draws, losses) = (self.wins as f64 + PRIOR, self.draws as f64, self.losses as f64 + PRIOR);
+        let total = wins + draws + losses;
+        let mean = (wins + 0.5 * draws) / total;
+        (wins * (1. - mean).powi(2) + draws * (0.5 - mean).powi(2) + losses * mean.powi(2)) / total
+    }
+
+    /// Half width of the 95% confidence interval around [`Record::elo`]
+    pub fn elo_error(&self) -> f64 {
+        let score = self.score();
+        let margin = 1.96 * (self.variance() / (self.games() as f64 + 2. * PRIOR)).sqrt();
+        // keep both ends inside (0, 1) so small samples give a wide but finite interval
+        let bound = |s: f64| s.clamp(SCORE_BOUND, 1. - SCORE_BOUND);
+        (elo_difference(bound(score + margin)) - elo_difference(bound(score - margin))) / 2.
+    }
+}
+
+impl std::ops::AddAssign for Record {
+    fn add_assign(&mut self, other: Record) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+}
+
+/// Pseudo wins and losses added to a record before measuring its spread
+const PRIOR: f64 = 0.5;
+
+/// Closest a record's score is taken to 0 or 1, about ±1200 Elo
+const SCORE_BOUND: f64 = 0.001;
+
+/// Elo difference that makes `score` the expected score per game
+pub fn elo_difference(score: f64) -> f64 {
+    400. * (score / (1. - score)).log10()
+}
+
+/// Ratings of every player that has played a game, keyed by player name,
+/// saved as a csv file between runs
+pub struct RatingsDb {
+    path: PathBuf,
+    pub ratings: BTreeMap<String, Rating>,
+    /// Games since the last update as (white, black, white's score)
+    pending: Vec<(String, String, f64)>,
+}
+
+impl RatingsDb {
+    /// The shared database in `~/.chessers/ratings.csv`
+    pub fn open_default() -> RatingsDb {
+        RatingsDb::open(format!("{}/ratings.csv", resources_dir()))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> RatingsDb {
+        let path = path.as_ref().to_path_buf();
+        let mut ratings = BTreeMap::new();
+        if let Ok(mut reader) = csv::Reader::from_path(&path) {
+            for record in reader.records() {
+                let row = record.expect("Corrupt ratings file");
+                let field = |i: usize| row.get(i).unwrap().parse::<f64>().expect("Corrupt ratings file");
+                ratings.insert(row.get(0).unwrap().to_string(), Rating {
+                    rating: field(1),
+                    deviation: field(2),
+                    volatility: field(3),
+                    games: row.get(4).unwrap().parse().expect("Corrupt ratings file"),
+                });
+            }
+        }
+        RatingsDb { path, ratings, pending: vec!() }
+    }
+
+    pub fn get(&self, player: &str) -> Rating {
+        self.ratings.get(player).copied().unwrap_or_default()
+    }
+
+    /// Queue a game for the next [`RatingsDb::update`]
+    pub fn record(&mut self, white: &str, black: &str, white_score: f64) {
+        self.pending.push((white.to_string(), black.to_string(), white_score));
+    }
+
+    /// Treat every game recorded since the last update as one rating period
+    /// and update everybody who played in it
+    pub fn update(&mut self) {
+        let mut results: BTreeMap<String, Vec<(Rating, f64)>> = BTreeMap::new();
+        for (white, black, score) in self.pending.drain(..) {
+            let white_rating = self.ratings.get(&white).copied().unwrap_or_default();
+            let black_rating = self.ratings.get(&black).copied().unwrap_or_default();
+            results.entry(white).or_default().push((black_rating, score));
+            results.entry(black).or_default().push((white_rating, 1. - score));
+        }
+        for (player, games) in results {
+            let rating = self.get(&player).update(&games);
+            self.ratings.insert(player, rating);
+        }
+    }
+
+    /// Write the database, going through a temporary file so a crash or a
+    /// concurrent reader never sees half a file
+    pub fn save(&self) {
+        if let Some(dir) = self.path.parent() {
+            create_dir_all(dir).expect("Error creating ratings directory");
+        }
+        // unique per save, so databases saved at the same time never share a temporary file
+        let tmp = self.path.with_extension(format!("{}.{}.tmp", std::process::id(), SAVES.fetch_add(1, Ordering::Relaxed)));
+        let mut writer = csv::Writer::from_path(&tmp)
+            .unwrap_or_else(|_| panic!("Failed to open {:?} for writing", &tmp));
+        writer.write_record(["Player", "Rating", "Deviation", "Volatility", "Games"])
+            .expect("Error writing ratings");
+        for (player, rating) in &self.ratings {
+            writer.write_record(&[
+                player.clone(),
+                rating.rating.to_string(),
+                rating.deviation.to_string(),
+                rating.volatility.to_string(),
+                rating.games.to_string(),
+            ]).expect("Error writing ratings");
+        }
+        writer.flush().unwrap();
+        rename(&tmp, &self.path).expect("Error replacing ratings file");
+    }
+
+    /// Apply pending games and save, the usual way to finish a batch of games
+    pub fn commit(&mut self) {
+        self.update();
+        self.save();
+    }
+
+    /// Players sorted from highest to lowest rating
+    pub fn ranked(&self) -> Vec<(&String, &Rating)> {
+        let mut ranked: Vec<(&String, &Rating)> = self.ratings.iter().collect();
+        ranked.sort_by(|(_, a), (_, b)| b.rating.total_cmp(&a.rating));
+        ranked
+    }
+}
+
+/// Name a player is rated under: model files by their absolute path so the
+/// same file matches however it was passed on the command line
+pub fn player_id(source: &str) -> String {
+    match std::fs::canonicalize(source) {
+        Ok(path) if path.is_file() => path.to_string_lossy().to_string(),
+        _ => source.to_string(),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::rating::{elo_difference, Rating, RatingsDb, Record};
+
+    #[test]
+    fn even_score_is_even_elo() {
+        assert_eq!(elo_difference(0.5), 0.);
+        assert!((elo_difference(0.75) - 190.85).abs() < 0.01);
+    }
+
+    #[test]
+    fn record_counts_results() {
+        let mut record = Record::default();
+        for score in [1., 1., 0.5, 0.] {
+            record.add(score);
+        }
+        assert_eq!(record, Record { wins: 2, draws: 1, losses: 1 });
+        assert_eq!(record.score(), 0.625);
+        assert!(record.elo() > 0.);
+        assert!(record.elo_error() > 0.);
+    }
+
+    #[test]
+    fn sweeps_and_empty_records_stay_uncertain() {
+        let sweep = Record { wins: 2, draws: 0, losses: 0 };
+        assert!(sweep.elo().is_finite() && sweep.elo() > 1000.);
+        let whitewash = Record { wins: 0, draws: 0, losses: 2 };
+        assert!((whitewash.elo() + sweep.elo()).abs() < 1e-9);
+        assert_eq!(Record::default().elo(), 0.);
+        assert!(Record::default().elo_error().is_finite());
+        // a few identical results are far from conclusive
+        assert!(Record { wins: 5, draws: 0, losses: 0 }.elo_error() > 300.);
+        assert!(Record { wins: 0, draws: 0, losses: 5 }.elo_error() > 300.);
+        assert!(Record { wins: 0, draws: 5, losses: 0 }.elo_error() > 100.);
+        assert!(Record { wins: 500, draws: 0, losses: 500 }.elo_error() < 50.);
+    }
+
+    #[test]
+    fn glickman_example() {
+        // worked example from Glickman's "Example of the Glicko-2 system"
+        let player = Rating { rating: 1500., deviation: 200., volatility: 0.06, games: 0 };
+        let opponent = |rating, deviation| Rating { rating, deviation, ..Default::default() };
+        let updated = player.update(&[
+            (opponent(1400., 30.), 1.),
+            (opponent(1550., 100.), 0.),
+            (opponent(1700., 300.), 0.),
+        ]);
+        assert!((updated.rating - 1464.06).abs() < 0.01, "{}", updated.rating);
+        assert!((updated.deviation - 151.52).abs() < 0.01, "{}", updated.deviation);
+        assert!((updated.volatility - 0.05999).abs() < 0.00001, "{}", updated.volatility);
+        assert_eq!(updated.games, 3);
+    }
+
+    #[test]
+    fn idle_players_grow_uncertain() {
+        let player = Rating { deviation: 50., ..Default::default() };
+        assert!(player.update(&[]).deviation > 50.);
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let path = std::env::temp_dir().join("chessers_ratings_test.csv");
+        let mut db = RatingsDb::open(&path);
+        db.ratings.clear();
+        db.record("a", "b", 1.);
+        db.record("b", "a", 0.5);
+        db.commit();
+        assert!(db.get("a").rating > db.get("b").rating);
+        assert_eq!(db.ranked()[0].0, "a");
+
+        let reloaded = RatingsDb::open(&path);
+        assert_eq!(reloaded.get("a"), db.get("a"));
+        assert_eq!(reloaded.get("b").games, 2);
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use crate::baseline::BENCHMARK_DEPTH;
+use crate::nn::{ChessNet, SampledNet};
+use crate::player::{GreedyPlayer, HumanPlayer, MaterialPlayer, MinimaxPlayer, Player, RandomPlayer};
+use crate::rating::player_id;
+
+const KINDS: &str = "human, random, greedy, material, minimax or net, or a .safetensors file";
+
+/// A player named on the command line or in the browser: a kind, then
+/// colon separated arguments, e.g. `random:seed=42`, `minimax:depth=3`
+/// (or just `minimax:3`) and `net:model.safetensors:temp=0.5`. A bare
+/// safetensors path is short for `net:` and the path.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayerSpec {
+    Human,
+    Random { seed: Option<u64> },
+    Greedy,
+    Material,
+    Minimax { depth: u32 },
+    /// A saved network, sampling its moves when the temperature is above 0
+    Net { path: String, temperature: f64, seed: Option<u64> },
+}
+
+impl PlayerSpec {
+    /// Create the player, which fails if a model file can't be loaded
+    pub fn build(&self) -> Result<Box<dyn Player>, String> {
+        Ok(match self {
+            PlayerSpec::Human => Box::new(HumanPlayer::default()),
+            PlayerSpec::Random { seed } => Box::new(RandomPlayer::new(*seed)),
+            PlayerSpec::Greedy => Box::new(GreedyPlayer),
+            PlayerSpec::Material => Box::new(MaterialPlayer),
+            PlayerSpec::Minimax { depth } => Box::new(MinimaxPlayer { depth: *depth }),
+            PlayerSpec::Net { path, temperature, .. } if *temperature == 0. => Box::new(ChessNet::load(path)?),
+            PlayerSpec::Net { path, temperature, seed } => Box::new(SampledNet::new(ChessNet::load(path)?, *temperature, *seed)),
+        })
+    }
+}
+
+/// Name a player is rated under: a network's canonical model file however
+/// the spec was written, otherwise the spec itself
+pub fn rating_id(source: &str) -> String {
+    match source.parse() {
+        Ok(PlayerSpec::Net { path, .. }) => player_id(&path),
+        _ => player_id(source),
+    }
+}
+
+/// The arguments after a spec's kind, consumed as each kind reads them
+struct Arguments<'a> {
+    spec: &'a str,
+    kind: &'a str,
+    positional: Vec<&'a str>,
+    named: BTreeMap<&'a str, &'a str>,
+}
+
+impl<'a> Arguments<'a> {
+    fn new(spec: &'a str, kind: &'a str, arguments: &[&'a str]) -> Result<Arguments<'a>, String> {
+        let mut positional = Vec::new();
+        let mut named = BTreeMap::new();
+        for argument in arguments {
+            match argument.split_once('=') {
+                Some((key, value)) => if named.insert(key, value).is_some() {
+                    return Err(format!("{} is given twice in player {:?}", key, spec));
+                },
+                None => positional.push(*argument),
+            }
+        }
+        Ok(Arguments { spec, kind, positional, named })
+    }
+
+    /// The next argument given without a name
+    fn positional(&mut self) -> Option<&'a str> {
+        (!self.positional.is_empty()).then(|| self.positional.remove(0))
+    }
+
+    /// Take an option, which may also be given as the next bare argument
+    fn value<T: FromStr>(&mut self, key: &str, bare: Option<&'a str>) -> Result<Option<T>, String> {
+        let text = match (bare, self.named.remove(key)) {
+            (Some(_), Some(_)) => return Err(format!("{} is given twice in player {:?}", key, self.spec)),
+            (text, None) | (None, text) => text,
+        };
+        text.map(|text| text.parse().map_err(|_| format!("Invalid {} {:?} in player {:?}", key, text, self.spec)))
+            .transpose()
+    }
+
+    /// Fail on anything the kind didn't take
+    fn finish(self) -> Result<(), String> {
+        if let Some(extra) = self.positional.first() {
+            return Err(format!("Unexpected {:?} in player {:?}", extra, self.spec));
+        }
+        match self.named.keys().next() {
+            Some(key) => Err(format!("{} has no option {:?} in player {:?}", self.kind, key, self.spec)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for PlayerSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split(':').collect();
+        if parts[0].ends_with(".safetensors") {
+            parts.insert(0, "net");
+        }
+        let mut arguments = Arguments::new(s, parts[0], &parts[1..])?;
+        let spec = match parts[0] {
+            "human" => PlayerSpec::Human,
+            "greedy" => PlayerSpec::Greedy,
+            "material" => PlayerSpec::Material,
+            "random" => PlayerSpec::Random { seed: arguments.value("seed", None)? },
+            "minimax" => {
+                let bare = arguments.positional();
+                let depth = arguments.value("depth", bare)?.unwrap_or(BENCHMARK_DEPTH);
+                if depth == 0 {
+                    return Err(format!("Minimax depth must be at least 1 in player {:?}", s));
+                }
+                PlayerSpec::Minimax { depth }
+            },
+            "net" => {
+                let path = arguments.positional()
+                    .ok_or_else(|| format!("No model file in player {:?}, e.g. net:model.safetensors", s))?;
+                if !Path::new(path).is_file() {
+                    return Err(format!("No model file at {:?}", path));
+                }
+                let temperature = arguments.value("temp", None)?.unwrap_or(0.);
+                if !(temperature >= 0. && f64::is_finite(temperature)) {
+                    return Err(format!("Temperature must be 0 or more in player {:?}", s));
+                }
+                PlayerSpec::Net { path: path.to_string(), temperature, seed: arguments.value("seed", None)? }
+            },
+            kind => return Err(format!("Unknown player {:?}, expected {}", kind, KINDS)),
+        };
+        arguments.finish()?;
+        Ok(spec)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use candle_nn::VarMap;
+    use chess::Board;
+    use crate::nn::ChessNet;
+    use crate::player::Action;
+    use crate::rating::player_id;
+    use crate::spec::{rating_id, PlayerSpec};
+
+    fn parse(spec: &str) -> Result<PlayerSpec, String> {
+        spec.parse()
+    }
+
+    #[test]
+    fn kinds_and_options() {
+        assert_eq!(parse("human"), Ok(PlayerSpec::Human));
+        assert_eq!(parse("random"), Ok(PlayerSpec::Random { seed: None }));
+        assert_eq!(parse("random:seed=42"), Ok(PlayerSpec::Random { seed: Some(42) }));
+        assert_eq!(parse("greedy"), Ok(PlayerSpec::Greedy));
+        assert_eq!(parse("minimax"), Ok(PlayerSpec::Minimax { depth: 2 }));
+        assert_eq!(parse("minimax:3"), Ok(PlayerSpec::Minimax { depth: 3 }));
+        assert_eq!(parse("minimax:depth=4"), Ok(PlayerSpec::Minimax { depth: 4 }));
+
+        let file = std::env::temp_dir().join("chessers_spec.safetensors");
+        let file = file.to_str().unwrap();
+        ChessNet::new(VarMap::new()).save(file.to_string());
+        let net = |temperature, seed| Ok(PlayerSpec::Net { path: file.to_string(), temperature, seed });
+        assert_eq!(parse(file), net(0., None));
+        assert_eq!(parse(&format!("net:{}:temp=0.5", file)), net(0.5, None));
+        assert_eq!(parse(&format!("{}:temp=2:seed=1", file)), net(2., Some(1)));
+        let seeded = parse(&format!("net:{}:temp=5:seed=3", file)).unwrap();
+        assert_eq!(seeded.build().unwrap().make_move(&Board::default()), seeded.build().unwrap().make_move(&Board::default()));
+
+        // however a network is named, its games count towards the one model
+        for spec in [file.to_string(), format!("net:{}", file), format!("net:{}:temp=0.5", file)] {
+            assert_eq!(rating_id(&spec), player_id(file));
+        }
+        assert_eq!(rating_id("random:seed=42"), "random:seed=42");
+    }
+
+    #[test]
+    fn clear_errors() {
+        let error = |spec: &str| parse(spec).unwrap_err();
+        assert!(error("stockfish").starts_with("Unknown player \"stockfish\""));
+        assert_eq!(error("random:seed=abc"), "Invalid seed \"abc\" in player \"random:seed=abc\"");
+        assert_eq!(error("random:depth=3"), "random has no option \"depth\" in player \"random:depth=3\"");
+        assert_eq!(error("greedy:3"), "Unexpected \"3\" in player \"greedy:3\"");
+        assert_eq!(error("minimax:3:depth=4"), "depth is given twice in player \"minimax:3:depth=4\"");
+        assert!(error("minimax:0").contains("at least 1"));
+        assert_eq!(error("net"), "No model file in player \"net\", e.g. net:model.safetensors");
+        assert_eq!(error("missing.safetensors"), "No model file at \"missing.safetensors\"");
+        assert!(matches!(parse("random:seed=1").unwrap().build().unwrap().make_move(&Board::default()), Action::Move(_)));
+    }
+}
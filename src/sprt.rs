@@ -0,0 +1,166 @@
+use rand::thread_rng;
+use crate::adjudication::Adjudication;
+use crate::arena::play_from;
+use crate::clock::TimeControl;
+use crate::nn::ChessNet;
+use crate::openings;
+use crate::rating::{player_id, RatingsDb, Record};
+
+/// Random half moves played after each book opening, so deterministic
+/// players don't just repeat the same few games
+const RANDOM_PLIES: usize = 2;
+
+/// What the test concluded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    /// A is no better than `elo0`
+    AcceptH0,
+    /// A is at least `elo1` better
+    AcceptH1,
+    /// Not enough evidence yet
+    Continue,
+}
+
+/// Sequential probability ratio test of whether A is `elo1` rather than
+/// `elo0` Elo stronger than B, with false positive rate `alpha` and false
+/// negative rate `beta`
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// A test that can reach either decision: both error rates strictly
+    /// between 0 and 1, and `elo1` above `elo0`
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Result<Sprt, String> {
+        for (name, rate) in [("alpha", alpha), ("beta", beta)] {
+            if !(rate > 0. && rate < 1.) {
+                return Err(format!("{} must be between 0 and 1, not {}", name, rate));
+            }
+        }
+        if elo0.is_nan() || elo1.is_nan() || elo1 <= elo0 {
+            return Err(format!("elo1 ({}) must be greater than elo0 ({})", elo1, elo0));
+        }
+        Ok(Sprt { elo0, elo1, alpha, beta })
+    }
+
+    /// Log-likelihood ratio bounds for accepting H0 and H1
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1. - self.alpha)).ln(), ((1. - self.beta) / self.alpha).ln())
+    }
+
+    /// Log-likelihood ratio of H1 over H0 for A's record, using the normal
+    /// approximation to the trinomial distribution of game results
+    pub fn llr(&self, record: &Record) -> f64 {
+        let n = record.games() as f64;
+        if n == 0. {
+            return 0.;
+        }
+        // the variance has a prior so identical results can still decide, but the score is as played
+        let score = record.score();
+        let variance = record.variance();
+        let s0 = expected_score(self.elo0);
+        let s1 = expected_score(self.elo1);
+        n * (s1 - s0) * (2. * score - s0 - s1) / (2. * variance)
+    }
+
+    pub fn decide(&self, record: &Record) -> Decision {
+        let llr = self.llr(record);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Decision::AcceptH1
+        } else if llr <= lower {
+            Decision::AcceptH0
+        } else {
+            Decision::Continue
+        }
+    }
+
+    /// Play pairs of games between two models until the test decides or
+    /// `max_games` is reached, printing progress after every pair
+    pub fn run(&self, a_file: &str, b_file: &str, max_games: usize, adjudication: &Adjudication,
+               time_control: Option<TimeControl>, ratings: &mut RatingsDb) -> (Decision, Record) {
+        let a = ChessNet::from_file(a_file);
+        let b = ChessNet::from_file(b_file);
+        let (a_id, b_id) = (player_id(a_file), player_id(b_file));
+        let (lower, upper) = self.bounds();
+        let mut rng = thread_rng();
+        let mut record = Record::default();
+        let mut decision = Decision::Continue;
+        while decision == Decision::Continue && (record.games() as usize) < max_games {
+            let opening = openings::randomise(&openings::sample(1, &mut rng)[0], RANDOM_PLIES, &mut rng);
+            let white_score = play_from(&a, &b, &opening, adjudication, time_control).outcome.white_score();
+            ratings.record(&a_id, &b_id, white_score);
+            record.add(white_score);
+            let white_score = play_from(&b, &a, &opening, adjudication, time_control).outcome.white_score();
+            ratings.record(&b_id, &a_id, white_score);
+            record.add(1. - white_score);
+
+            decision = self.decide(&record);
+            println!("Games {:>5}: +{} ={} -{}  LLR {:>6.2} [{:.2}, {:.2}]",
+                     record.games(), record.wins, record.draws, record.losses, self.llr(&record), lower, upper);
+        }
+        ratings.commit();
+        (decision, record)
+    }
+}
+
+/// Expected score per game for a player `elo` points stronger
+pub fn expected_score(elo: f64) -> f64 {
+    1. / (1. + 10f64.powf(-elo / 400.))
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::rating::Record;
+    use crate::sprt::{expected_score, Decision, Sprt};
+
+    const TEST: Sprt = Sprt { elo0: 0., elo1: 10., alpha: 0.05, beta: 0.05 };
+
+    #[test]
+    fn bounds_match_wald() {
+        let (lower, upper) = TEST.bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+    }
+
+    #[test]
+    fn expected_scores() {
+        assert_eq!(expected_score(0.), 0.5);
+        assert!(expected_score(10.) > 0.5);
+    }
+
+    #[test]
+    fn decides_clear_results() {
+        let strong = Record { wins: 600, draws: 200, losses: 200 };
+        assert!(TEST.llr(&strong) > 0.);
+        assert_eq!(TEST.decide(&strong), Decision::AcceptH1);
+        let weak = Record { wins: 200, draws: 200, losses: 600 };
+        assert_eq!(TEST.decide(&weak), Decision::AcceptH0);
+        let early = Record { wins: 2, draws: 1, losses: 1 };
+        assert_eq!(TEST.decide(&early), Decision::Continue);
+        assert!(TEST.llr(&Record { wins: 0, draws: 10, losses: 0 }) < 0.);
+    }
+
+    #[test]
+    fn rejects_degenerate_tests() {
+        assert!(Sprt::new(0., 10., 0.05, 0.05).is_ok());
+        assert!(Sprt::new(0., 10., 0., 0.05).unwrap_err().starts_with("alpha"));
+        assert!(Sprt::new(0., 10., 0.05, 1.).unwrap_err().starts_with("beta"));
+        assert!(Sprt::new(0., 10., f64::NAN, 0.05).is_err());
+        assert!(Sprt::new(10., 10., 0.05, 0.05).is_err());
+        assert!(Sprt::new(10., 0., 0.05, 0.05).is_err());
+    }
+
+    #[test]
+    fn decides_identical_results() {
+        assert_eq!(TEST.decide(&Record { wins: 100, draws: 0, losses: 0 }), Decision::AcceptH1);
+        assert_eq!(TEST.decide(&Record { wins: 0, draws: 200, losses: 0 }), Decision::AcceptH0);
+        assert_eq!(TEST.decide(&Record { wins: 0, draws: 0, losses: 100 }), Decision::AcceptH0);
+        assert_eq!(TEST.decide(&Record { wins: 1, draws: 0, losses: 0 }), Decision::Continue);
+    }
+}
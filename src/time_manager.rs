@@ -0,0 +1,202 @@
+use std::time::{Duration, Instant};
+use chess::{Board, ChessMove, Color};
+use crate::arena::material;
+use crate::clock::TimeLeft;
+
+/// Kept back from every allocation so a player never flags on overhead
+const SAFETY_MARGIN: Duration = Duration::from_millis(20);
+
+/// Everything that limits one search: the clock, if any, plus the
+/// `go movetime/depth/nodes` style overrides. All empty means no limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    pub time: Option<TimeLeft>,
+    /// Spend exactly this long, ignoring the clock
+    pub movetime: Option<Duration>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+}
+
+/// Decides when an iterative search should stop. The soft limit is checked
+/// between iterations and stretches when the best move keeps changing; the
+/// hard limit aborts a search mid iteration.
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+    depth: Option<u32>,
+    nodes: Option<u64>,
+    best: Option<ChessMove>,
+    /// Iterations in a row that ended on the same best move
+    stable: u32,
+    changed: bool,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, board: &Board) -> TimeManager {
+        let (soft, hard) = match (limits.movetime, limits.time) {
+            (Some(movetime), _) => (Some(movetime), Some(movetime)),
+            (None, Some(time)) => {
+                let (soft, hard) = allocate(&time, board);
+                (Some(soft), Some(hard))
+            },
+            (None, None) => (None, None),
+        };
+        TimeManager {
+            start: Instant::now(),
+            soft,
+            hard,
+            depth: limits.depth,
+            nodes: limits.nodes,
+            best: None,
+            stable: 0,
+            changed: false,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Soft limit for the current move stability
+    pub fn soft_limit(&self) -> Option<Duration> {
+        let factor = if self.changed {
+            1.5
+        } else if self.stable >= 3 {
+            0.6
+        } else {
+            1.
+        };
+        self.soft.map(|soft| {
+            let stretched = soft.mul_f64(factor);
+            self.hard.map_or(stretched, |hard| stretched.min(hard))
+        })
+    }
+
+    #[cfg(test)]
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+
+    /// Whether the search has to stop right now, mid iteration
+    pub fn must_stop(&self, nodes: u64) -> bool {
+        self.nodes.is_some_and(|limit| nodes >= limit)
+            || self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+
+    /// Report a finished iteration and decide whether to start the next one
+    pub fn next_iteration(&mut self, depth: u32, best: ChessMove, nodes: u64) -> bool {
+        self.changed = self.best.is_some_and(|previous| previous != best);
+        self.stable = if self.changed { 0 } else { self.stable + 1 };
+        self.best = Some(best);
+        if self.depth.is_some_and(|limit| depth >= limit) || self.must_stop(nodes) {
+            return false;
+        }
+        // the next iteration usually takes longer than all the previous ones
+        // together, so don't start one past half the soft limit
+        self.soft_limit().is_none_or(|soft| self.elapsed() < soft / 2)
+    }
+
+    /// True when nothing but the caller's own defaults limits the search
+    pub fn is_unlimited(&self) -> bool {
+        self.soft.is_none() && self.depth.is_none() && self.nodes.is_none()
+    }
+}
+
+/// Soft and hard limits for one move from the clock. The remaining time is
+/// spread over the moves still to play, estimated from the material left
+/// when the time control doesn't say, plus most of the increment.
+pub fn allocate(time: &TimeLeft, board: &Board) -> (Duration, Duration) {
+    let usable = time.remaining.saturating_sub(SAFETY_MARGIN);
+    let moves_to_go = match time.moves_to_go {
+        Some(moves) => moves as f64,
+        None => {
+            // 78 pawns of material at the start, so roughly 40 moves to go
+            // in the opening, falling towards 15 in a bare endgame
+            let phase = (material(board, Color::White) + material(board, Color::Black)) as f64 / 78.;
+            15. + 25. * phase.min(1.)
+        },
+    };
+    let soft = (usable.div_f64(moves_to_go) + time.increment.mul_f64(0.8)).min(usable);
+    let hard = (soft * 4).min(usable.mul_f64(0.5)).max(soft.min(usable));
+    (soft, hard)
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use std::time::Duration;
+    use chess::{Board, ChessMove, Square};
+    use crate::clock::TimeLeft;
+    use crate::time_manager::{allocate, SearchLimits, TimeManager};
+
+    fn time(seconds: u64, increment: u64, moves_to_go: Option<u32>) -> TimeLeft {
+        TimeLeft {
+            remaining: Duration::from_secs(seconds),
+            opponent: Duration::from_secs(seconds),
+            increment: Duration::from_secs(increment),
+            moves_to_go,
+        }
+    }
+
+    #[test]
+    fn spends_more_in_the_endgame() {
+        let endgame = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let (opening, _) = allocate(&time(60, 0, None), &Board::default());
+        let (late, _) = allocate(&time(60, 0, None), &endgame);
+        assert!(late > opening);
+        assert!(opening > Duration::from_secs(1));
+    }
+
+    #[test]
+    fn sessions_and_increments() {
+        let (soft, hard) = allocate(&time(100, 0, Some(10)), &Board::default());
+        assert!(soft > Duration::from_millis(9900) && soft < Duration::from_secs(10));
+        assert!(hard > soft);
+        let (with_increment, _) = allocate(&time(100, 5, Some(10)), &Board::default());
+        assert!(with_increment > soft);
+    }
+
+    #[test]
+    fn never_allocates_more_than_remaining() {
+        let (soft, hard) = allocate(&time(0, 2, None), &Board::default());
+        assert_eq!(soft, Duration::ZERO);
+        assert_eq!(hard, Duration::ZERO);
+    }
+
+    #[test]
+    fn overrides() {
+        let board = Board::default();
+        let movetime = SearchLimits { movetime: Some(Duration::from_secs(2)), time: Some(time(1, 0, None)), ..Default::default() };
+        let manager = TimeManager::new(&movetime, &board);
+        assert_eq!(manager.hard_limit(), Some(Duration::from_secs(2)));
+
+        let best = ChessMove::new(Square::E2, Square::E4, None);
+        let mut depth = TimeManager::new(&SearchLimits { depth: Some(2), ..Default::default() }, &board);
+        assert!(depth.next_iteration(1, best, 10));
+        assert!(!depth.next_iteration(2, best, 20));
+
+        let nodes = TimeManager::new(&SearchLimits { nodes: Some(100), ..Default::default() }, &board);
+        assert!(!nodes.must_stop(99));
+        assert!(nodes.must_stop(100));
+        assert!(TimeManager::new(&SearchLimits::default(), &board).is_unlimited());
+    }
+
+    #[test]
+    fn unstable_moves_stretch_the_soft_limit() {
+        let board = Board::default();
+        let limits = SearchLimits { time: Some(time(60, 0, Some(10))), ..Default::default() };
+        let mut manager = TimeManager::new(&limits, &board);
+        let base = manager.soft_limit().unwrap();
+        let e4 = ChessMove::new(Square::E2, Square::E4, None);
+        let d4 = ChessMove::new(Square::D2, Square::D4, None);
+        manager.next_iteration(1, e4, 1);
+        manager.next_iteration(2, d4, 1);
+        assert!(manager.soft_limit().unwrap() > base);
+        for depth in 3..6 {
+            manager.next_iteration(depth, d4, 1);
+        }
+        assert!(manager.soft_limit().unwrap() < base);
+    }
+}
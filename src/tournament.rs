@@ -0,0 +1,283 @@
+use std::fs::{create_dir_all, File};
+use std::io::BufWriter;
+use rand::thread_rng;
+use crate::adjudication::Adjudication;
+use crate::arena::{play_from, GameRecord};
+use crate::clock::TimeControl;
+use crate::openings;
+use crate::pgn;
+use crate::player::Player;
+use crate::rating::{RatingsDb, Record};
+
+/// How players are paired
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Everybody plays everybody `games` times, alternating colours
+    RoundRobin { games: usize },
+    /// Each round pairs players on similar scores who haven't met yet
+    Swiss { rounds: usize },
+}
+
+/// A game played in the tournament, by player index
+pub struct Pairing {
+    pub round: usize,
+    pub white: usize,
+    pub black: usize,
+    pub game: GameRecord,
+}
+
+/// Any number of players, known by the spec they were created from
+pub struct Tournament {
+    names: Vec<String>,
+    players: Vec<Box<dyn Player>>,
+    /// `results[i][j]` is player `i`'s record against player `j`
+    results: Vec<Vec<Record>>,
+    whites: Vec<usize>,
+    byes: Vec<usize>,
+    pub games: Vec<Pairing>,
+    /// Clock every game with this control
+    pub time_control: Option<TimeControl>,
+}
+
+impl Tournament {
+    pub fn new(entrants: Vec<(String, Box<dyn Player>)>) -> Tournament {
+        let n = entrants.len();
+        let (names, players) = entrants.into_iter().unzip();
+        Tournament {
+            names,
+            players,
+            results: vec![vec![Record::default(); n]; n],
+            whites: vec![0; n],
+            byes: vec![0; n],
+            games: vec!(),
+            time_control: None,
+        }
+    }
+
+    pub fn run(&mut self, format: Format, adjudication: &Adjudication, ratings: &mut RatingsDb) {
+        let mut rng = thread_rng();
+        match format {
+            Format::RoundRobin { games } => {
+                let n = self.players.len();
+                let schedule = round_robin_schedule(n);
+                let lines: Vec<Vec<_>> = (0..n * n).map(|_| openings::sample(games.div_ceil(2), &mut rng)).collect();
+                for cycle in 0..games {
+                    for (r, pairs) in schedule.iter().enumerate() {
+                        let round = cycle * schedule.len() + r + 1;
+                        for &(i, j) in pairs {
+                            let (white, black) = if cycle % 2 == 0 { (i, j) } else { (j, i) };
+                            self.play(round, white, black, &lines[i * n + j][cycle / 2], adjudication, ratings);
+                        }
+                    }
+                }
+            },
+            Format::Swiss { rounds } => {
+                for round in 1..=rounds {
+                    let (pairs, bye) = self.swiss_pairings();
+                    if let Some(player) = bye {
+                        self.byes[player] += 1;
+                    }
+                    for (white, black) in pairs {
+                        let opening = &openings::sample(1, &mut rng)[0];
+                        self.play(round, white, black, opening, adjudication, ratings);
+                    }
+                }
+            },
+        }
+        ratings.commit();
+    }
+
+    fn play(&mut self, round: usize, white: usize, black: usize, opening: &[chess::ChessMove], adjudication: &Adjudication, ratings: &mut RatingsDb) {
+        let game = play_from(self.players[white].as_ref(), self.players[black].as_ref(), opening, adjudication, self.time_control);
+        let score = game.outcome.white_score();
+        self.results[white][black].add(score);
+        self.results[black][white].add(1. - score);
+        self.whites[white] += 1;
+        ratings.record(&self.names[white], &self.names[black], score);
+        self.games.push(Pairing { round, white, black, game });
+    }
+
+    /// Tournament points, with a full point for every bye
+    pub fn points(&self) -> Vec<f64> {
+        self.results.iter().zip(&self.byes).map(|(row, &byes)| {
+            byes as f64 + row.iter().map(|r| r.wins as f64 + 0.5 * r.draws as f64).sum::<f64>()
+        }).collect()
+    }
+
+    /// Player indices from first place to last, ties broken by the
+    /// Sonneborn-Berger score (points taken off each opponent times their total)
+    pub fn standings(&self) -> Vec<usize> {
+        let points = self.points();
+        let tie_break: Vec<f64> = self.results.iter().map(|row| {
+            row.iter().zip(&points).map(|(r, p)| (r.wins as f64 + 0.5 * r.draws as f64) * p).sum()
+        }).collect();
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        order.sort_by(|&a, &b| points[b].total_cmp(&points[a]).then(tie_break[b].total_cmp(&tie_break[a])));
+        order
+    }
+
+    /// Pair the next Swiss round. Players are taken in standings order and
+    /// each meets the next player down they haven't played yet, falling back
+    /// to a rematch when nobody is left. With an odd field the lowest player
+    /// who hasn't had a bye sits out.
+    fn swiss_pairings(&self) -> (Vec<(usize, usize)>, Option<usize>) {
+        let mut order = self.standings();
+        let bye = if order.len() % 2 == 1 {
+            let position = order.iter().rposition(|&p| self.byes[p] == 0).unwrap_or(order.len() - 1);
+            Some(order.remove(position))
+        } else {
+            None
+        };
+        let mut pairs = vec!();
+        while let Some(first) = order.first().copied() {
+            order.remove(0);
+            let position = order.iter().position(|&p| self.results[first][p].games() == 0).unwrap_or(0);
+            let second = order.remove(position);
+            // whoever has had fewer whites gets white, the higher placed player on a tie
+            if self.whites[second] < self.whites[first] {
+                pairs.push((second, first));
+            } else {
+                pairs.push((first, second));
+            }
+        }
+        (pairs, bye)
+    }
+
+    pub fn print_standings(&self) {
+        let points = self.points();
+        println!("{:>4}  {:>6}  {:>5}  Player", "Rank", "Points", "Games");
+        for (rank, &i) in self.standings().iter().enumerate() {
+            let games: u32 = self.results[i].iter().map(Record::games).sum();
+            println!("{:>4}  {:>6.1}  {:>5}  {}", rank + 1, points[i], games, self.names[i]);
+        }
+    }
+
+    /// Write `crosstable.csv` and `games.pgn` into `dir`
+    pub fn save(&self, dir: &str) {
+        create_dir_all(dir).expect("Error creating tournament directory");
+        let points = self.points();
+        let order = self.standings();
+
+        let crosstable = format!("{}/crosstable.csv", dir);
+        let mut writer = csv::Writer::from_path(&crosstable)
+            .unwrap_or_else(|_| panic!("Failed to open {} for writing", &crosstable));
+        let mut header = vec!["Rank".to_string(), "Player".to_string()];
+        header.extend((1..=order.len()).map(|rank| rank.to_string()));
+        header.extend(["Byes".to_string(), "Points".to_string()]);
+        writer.write_record(&header).expect("Error writing crosstable");
+        for (rank, &i) in order.iter().enumerate() {
+            let mut row = vec![(rank + 1).to_string(), self.names[i].clone()];
+            row.extend(order.iter().map(|&j| {
+                let record = self.results[i][j];
+                if record.games() == 0 {
+                    String::new()
+                } else {
+                    (record.wins as f64 + 0.5 * record.draws as f64).to_string()
+                }
+            }));
+            row.extend([self.byes[i].to_string(), points[i].to_string()]);
+            writer.write_record(&row).expect("Error writing crosstable");
+        }
+        writer.flush().unwrap();
+
+        let pgn_file = format!("{}/games.pgn", dir);
+        let mut out = BufWriter::new(File::create(&pgn_file)
+            .unwrap_or_else(|_| panic!("Failed to open {} for writing", &pgn_file)));
+        let date = chrono::Utc::now().format("%Y.%m.%d").to_string();
+        for pairing in &self.games {
+            pgn::write_game(&mut out, &[
+                ("Event", "chessers tournament".to_string()),
+                ("Site", "chessers".to_string()),
+                ("Date", date.clone()),
+                ("Round", pairing.round.to_string()),
+                ("White", self.names[pairing.white].clone()),
+                ("Black", self.names[pairing.black].clone()),
+            ], &pairing.game).expect("Error writing games");
+        }
+    }
+}
+
+/// Rounds of a single round robin by the circle method, each pair with the
+/// lower index first. With an odd field one player sits out each round.
+fn round_robin_schedule(n: usize) -> Vec<Vec<(usize, usize)>> {
+    let m = n + n % 2;
+    (0..m.saturating_sub(1)).map(|round| {
+        (0..m / 2).map(|k| {
+            let a = (round + k) % (m - 1);
+            let b = if k == 0 { m - 1 } else { (round + m - 1 - k) % (m - 1) };
+            (a.min(b), a.max(b))
+        }).filter(|&(_, b)| b < n).collect()
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use candle_nn::VarMap;
+    use crate::adjudication::Adjudication;
+    use crate::nn::ChessNet;
+    use crate::player::{Player, RandomPlayer};
+    use crate::rating::RatingsDb;
+    use crate::tournament::{round_robin_schedule, Format, Tournament};
+
+    fn entrants(n: usize) -> Vec<(String, Box<dyn Player>)> {
+        (0..n).map(|i| {
+            let player: Box<dyn Player> = if i % 2 == 0 {
+                Box::new(ChessNet::new(VarMap::new()))
+            } else {
+                Box::new(RandomPlayer::default())
+            };
+            (format!("player {}", i), player)
+        }).collect()
+    }
+
+    fn ratings() -> RatingsDb {
+        RatingsDb::open(std::env::temp_dir().join("chessers_tournament_ratings.csv"))
+    }
+
+    #[test]
+    fn round_robin_plays_every_pair() {
+        let mut tournament = Tournament::new(entrants(3));
+        tournament.run(Format::RoundRobin { games: 2 }, &Adjudication::default(), &mut ratings());
+        assert_eq!(tournament.games.len(), 6);
+        assert_eq!(tournament.points().iter().sum::<f64>(), 6.);
+        let dir = std::env::temp_dir().join("chessers_tournament_test");
+        tournament.save(dir.to_str().unwrap());
+        let crosstable = std::fs::read_to_string(dir.join("crosstable.csv")).unwrap();
+        assert_eq!(crosstable.lines().count(), 4);
+        let pgn = std::fs::read_to_string(dir.join("games.pgn")).unwrap();
+        assert_eq!(pgn.matches("[Event ").count(), 6);
+        // one game a round with three players, the second cycle after the first
+        let rounds: Vec<usize> = tournament.games.iter().map(|p| p.round).collect();
+        assert_eq!(rounds, vec![1, 2, 3, 4, 5, 6]);
+        assert!(pgn.contains("[Round \"6\"]"));
+    }
+
+    #[test]
+    fn schedule_meets_everyone_once() {
+        for n in 2..=7 {
+            let schedule = round_robin_schedule(n);
+            assert_eq!(schedule.len(), n + n % 2 - 1);
+            let mut pairs: Vec<(usize, usize)> = schedule.iter().flatten().copied().collect();
+            for round in &schedule {
+                let mut players: Vec<usize> = round.iter().flat_map(|&(a, b)| [a, b]).collect();
+                players.sort();
+                players.dedup();
+                assert_eq!(players.len(), 2 * round.len());
+            }
+            assert_eq!(pairs.len(), n * (n - 1) / 2);
+            pairs.sort();
+            pairs.dedup();
+            assert_eq!(pairs.len(), n * (n - 1) / 2);
+        }
+    }
+
+    #[test]
+    fn swiss_gives_one_bye_per_round() {
+        let mut tournament = Tournament::new(entrants(5));
+        tournament.run(Format::Swiss { rounds: 3 }, &Adjudication::default(), &mut ratings());
+        assert_eq!(tournament.games.len(), 6);
+        assert_eq!(tournament.byes.iter().sum::<usize>(), 3);
+        assert!(tournament.byes.iter().all(|&b| b <= 1));
+        assert_eq!(tournament.points().iter().sum::<f64>(), 9.);
+    }
+}
@@ -0,0 +1,338 @@
+use std::cell::RefCell;
+use std::io::stdout;
+use chess::{Board, ChessMove, Color, MoveGen, Piece, Square, ALL_FILES, ALL_RANKS, File, Rank};
+use ratatui::{DefaultTerminal, Frame};
+use ratatui::crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+                                MouseButton, MouseEventKind};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{Clear, ClearType};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color as Colour, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use crate::clock::{format_time, Clock};
+use crate::nn::ChessNet;
+use crate::pgn::san;
+use crate::player::{parse_move, Action, Player};
+use crate::ui::{glyph, UI, CHECK, DARK, LAST_MOVE, LIGHT};
+
+/// Square picked up with the mouse
+const SELECTED: u8 = 110;
+/// Engine moves shown for each position
+const ENGINE_LINES: usize = 5;
+
+/// Everything on screen
+#[derive(Default)]
+struct State {
+    board: Board,
+    moves: Vec<String>,
+    last_move: Option<ChessMove>,
+    clock: Option<[String; 2]>,
+    engine: Vec<(String, f64)>,
+    input: String,
+    status: String,
+    selected: Option<Square>,
+    flipped: bool,
+    /// Where the squares were last drawn, to map mouse clicks back to them
+    board_area: Rect,
+}
+
+/// Full screen terminal UI with a board, move list, clocks and what a
+/// [`ChessNet`] thinks of the position. Humans play through a [`TerminalPlayer`].
+pub struct TerminalUI {
+    terminal: RefCell<DefaultTerminal>,
+    state: RefCell<State>,
+    engine: Option<ChessNet>,
+}
+
+impl TerminalUI {
+    /// Take over the terminal until dropped
+    pub fn new(engine: Option<ChessNet>, flipped: bool) -> TerminalUI {
+        let terminal = ratatui::init();
+        execute!(stdout(), EnableMouseCapture).expect("Couldn't capture the mouse");
+        let state = State { flipped, status: "Type a move or click the squares, Esc to clear, Ctrl+C to quit".to_string(), ..Default::default() };
+        TerminalUI { terminal: RefCell::new(terminal), state: RefCell::new(state), engine }
+    }
+
+    fn redraw(&self) {
+        let mut terminal = self.terminal.borrow_mut();
+        let mut state = self.state.borrow_mut();
+        // bots print their moves to stdout, so wipe the screen and blank the
+        // previous frame to repaint everything. Terminal::clear would ask the
+        // terminal where its cursor is, which not every terminal answers.
+        execute!(stdout(), Clear(ClearType::All)).expect("Couldn't clear the terminal");
+        terminal.swap_buffers();
+        terminal.draw(|frame| draw(frame, &mut state)).expect("Couldn't draw the terminal");
+    }
+
+    /// Read keys and clicks until the human decides what to do
+    fn read_action(&self, board: &Board) -> Action {
+        loop {
+            self.redraw();
+            let event = match event::read() {
+                Ok(event) => event,
+                Err(e) => return Action::Error(e.to_string()),
+            };
+            let mut state = self.state.borrow_mut();
+            match event {
+                Event::Key(key) if key.kind != KeyEventKind::Press => {},
+                Event::Key(key) if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') => {
+                    return Action::Error("quit".to_string());
+                },
+                Event::Key(key) => match key.code {
+                    KeyCode::Char(c) => state.input.push(c),
+                    KeyCode::Backspace => {
+                        state.input.pop();
+                    },
+                    KeyCode::Esc => {
+                        state.input.clear();
+                        state.selected = None;
+                    },
+                    KeyCode::Enter => {
+                        let text = std::mem::take(&mut state.input);
+                        match text.trim() {
+                            "undo" => return Action::TakeBack,
+                            "resign" => return Action::Resign,
+                            "draw" => return Action::OfferDraw,
+                            "accept" => return Action::AcceptDraw,
+                            "flip" => state.flipped = !state.flipped,
+                            text => match parse_move(board, text) {
+                                Some(chess_move) => return Action::Move(chess_move),
+                                None => state.status = format!("{} isn't a legal move; try undo, draw, accept, resign or flip", text),
+                            },
+                        }
+                    },
+                    _ => {},
+                },
+                Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                    let Some(square) = square_at(state.board_area, state.flipped, mouse.column, mouse.row) else {
+                        continue;
+                    };
+                    match state.selected.and_then(|source| find_move(board, source, square)) {
+                        Some(chess_move) => return Action::Move(chess_move),
+                        None if board.color_on(square) == Some(board.side_to_move()) => state.selected = Some(square),
+                        None => state.selected = None,
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+impl Drop for TerminalUI {
+    fn drop(&mut self) {
+        execute!(stdout(), DisableMouseCapture).ok();
+        ratatui::restore();
+    }
+}
+
+impl UI for TerminalUI {
+    fn update(&self, board: &Board, moves: &[ChessMove]) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.board = *board;
+            state.moves = sans(moves);
+            state.last_move = moves.last().copied();
+            state.selected = None;
+            state.engine = match &self.engine {
+                Some(engine) => engine.ranked_moves(board).into_iter()
+                    .take(ENGINE_LINES)
+                    .map(|(chess_move, score)| (san(board, chess_move), score))
+                    .collect(),
+                None => Vec::new(),
+            };
+        }
+        self.redraw();
+    }
+
+    fn show_clock(&self, clock: &Clock) {
+        self.state.borrow_mut().clock = Some([
+            format_time(clock.remaining(Color::White)),
+            format_time(clock.remaining(Color::Black)),
+        ]);
+        self.redraw();
+    }
+
+    fn message(&self, text: &str) {
+        self.state.borrow_mut().status = text.to_string();
+        self.redraw();
+    }
+
+    fn game_over(&self, result: &str) {
+        self.state.borrow_mut().status = format!("{}. Press any key to leave", result);
+        self.redraw();
+        while let Ok(event) = event::read() {
+            if matches!(event, Event::Key(key) if key.kind == KeyEventKind::Press) {
+                break;
+            }
+        }
+    }
+}
+
+/// A human at the [`TerminalUI`]
+pub struct TerminalPlayer {
+    pub ui: std::rc::Rc<TerminalUI>,
+}
+
+impl Player for TerminalPlayer {
+    fn make_move(&self, board: &Board) -> Action {
+        self.ui.read_action(board)
+    }
+}
+
+/// The game so far in SAN, from the starting position
+fn sans(moves: &[ChessMove]) -> Vec<String> {
+    let mut board = Board::default();
+    moves.iter()
+        .map(|&chess_move| {
+            let text = san(&board, chess_move);
+            board = board.make_move_new(chess_move);
+            text
+        })
+        .collect()
+}
+
+/// The legal move between two clicked squares, promoting to a queen
+fn find_move(board: &Board, source: Square, dest: Square) -> Option<ChessMove> {
+    MoveGen::new_legal(board)
+        .filter(|m| m.get_source() == source && m.get_dest() == dest)
+        .find(|m| m.get_promotion().is_none_or(|piece| piece == Piece::Queen))
+}
+
+/// Square under a click, for squares three columns wide after a two column rank label
+fn square_at(area: Rect, flipped: bool, column: u16, row: u16) -> Option<Square> {
+    let x = column.checked_sub(area.x + 2)? / 3;
+    let y = row.checked_sub(area.y)?;
+    if x >= 8 || y >= 8 {
+        return None;
+    }
+    let (file, rank) = if flipped { (7 - x, y) } else { (x, 7 - y) };
+    Some(Square::make_square(Rank::from_index(rank as usize), File::from_index(file as usize)))
+}
+
+fn draw(frame: &mut Frame, state: &mut State) {
+    let [main, input] = Layout::vertical([Constraint::Min(0), Constraint::Length(3)]).areas(frame.area());
+    let [board_pane, side] = Layout::horizontal([Constraint::Length(28), Constraint::Min(20)]).areas(main);
+    let [clocks, moves, engine] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Min(3),
+        Constraint::Length(ENGINE_LINES as u16 + 2),
+    ]).areas(side);
+
+    let block = Block::bordered().title("Board");
+    state.board_area = block.inner(board_pane);
+    frame.render_widget(Paragraph::new(board_lines(state)).block(block), board_pane);
+
+    let clock_lines: Vec<Line> = match &state.clock {
+        Some([white, black]) => vec![Line::from(format!("White {}", white)), Line::from(format!("Black {}", black))],
+        None => vec![Line::from("No clock")],
+    };
+    frame.render_widget(Paragraph::new(clock_lines).block(Block::bordered().title("Clock")), clocks);
+
+    let numbered: Vec<Line> = state.moves.chunks(2).enumerate()
+        .map(|(i, pair)| Line::from(format!("{:>3}. {:<8}{}", i + 1, pair[0], pair.get(1).map_or("", String::as_str))))
+        .collect();
+    // keep the latest moves in view
+    let hidden = numbered.len().saturating_sub(moves.height.saturating_sub(2) as usize);
+    frame.render_widget(Paragraph::new(numbered[hidden..].to_vec()).block(Block::bordered().title("Moves")), moves);
+
+    let engine_lines: Vec<Line> = state.engine.iter()
+        .map(|(text, score)| Line::from(format!("{:<8}{:>9.4}", text, score)))
+        .collect();
+    frame.render_widget(Paragraph::new(engine_lines).block(Block::bordered().title("Engine")), engine);
+
+    let prompt = Paragraph::new(format!("> {}", state.input)).block(Block::bordered().title(state.status.as_str()));
+    frame.render_widget(prompt, input);
+}
+
+fn board_lines(state: &State) -> Vec<Line<'static>> {
+    let board = &state.board;
+    let mut ranks = ALL_RANKS.to_vec();
+    let mut files = ALL_FILES.to_vec();
+    if state.flipped {
+        files.reverse();
+    } else {
+        ranks.reverse();
+    }
+    let checked = match board.checkers().popcnt() {
+        0 => None,
+        _ => Some(board.king_square(board.side_to_move())),
+    };
+    let mut lines: Vec<Line> = ranks.iter()
+        .map(|&rank| {
+            let mut spans = vec![Span::raw(format!("{} ", rank.to_index() + 1))];
+            for &file in &files {
+                let square = Square::make_square(rank, file);
+                let background = if state.selected == Some(square) {
+                    SELECTED
+                } else if Some(square) == checked {
+                    CHECK
+                } else if state.last_move.is_some_and(|m| m.get_source() == square || m.get_dest() == square) {
+                    LAST_MOVE
+                } else if (rank.to_index() + file.to_index()) % 2 == 1 {
+                    LIGHT
+                } else {
+                    DARK
+                };
+                let (foreground, text) = match board.piece_on(square).zip(board.color_on(square)) {
+                    Some((piece, color)) => (if color == Color::White { 231 } else { 16 }, glyph(piece, Color::Black)),
+                    None => (16, ' '),
+                };
+                let style = Style::default().bg(Colour::Indexed(background)).fg(Colour::Indexed(foreground));
+                spans.push(Span::styled(format!(" {} ", text), style));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    let labels: String = files.iter().map(|file| format!(" {} ", (b'a' + file.to_index() as u8) as char)).collect();
+    lines.push(Line::from(format!("  {}", labels)));
+    lines
+}
+
+
+#[cfg(test)]
+mod test {
+    use chess::{Board, ChessMove, Square};
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use ratatui::layout::Rect;
+    use crate::tui::{draw, find_move, sans, square_at, State};
+
+    #[test]
+    fn clicks_map_to_squares() {
+        let area = Rect::new(1, 1, 26, 9);
+        assert_eq!(square_at(area, false, 3, 1), Some(Square::A8));
+        assert_eq!(square_at(area, false, 5, 8), Some(Square::A1));
+        assert_eq!(square_at(area, false, 24, 8), Some(Square::H1));
+        assert_eq!(square_at(area, true, 3, 1), Some(Square::H1));
+        assert_eq!(square_at(area, false, 2, 1), None);
+        assert_eq!(square_at(area, false, 3, 9), None);
+    }
+
+    #[test]
+    fn clicked_moves() {
+        let board = Board::default();
+        assert_eq!(find_move(&board, Square::G1, Square::F3), Some(ChessMove::new(Square::G1, Square::F3, None)));
+        assert_eq!(find_move(&board, Square::G1, Square::G3), None);
+    }
+
+    #[test]
+    fn panes() {
+        let moves = [ChessMove::new(Square::E2, Square::E4, None), ChessMove::new(Square::E7, Square::E5, None)];
+        let mut state = State {
+            board: Board::default().make_move_new(moves[0]).make_move_new(moves[1]),
+            moves: sans(&moves),
+            last_move: Some(moves[1]),
+            engine: vec![("Nf3".to_string(), 1.5)],
+            ..Default::default()
+        };
+        let mut terminal = Terminal::new(TestBackend::new(60, 20)).unwrap();
+        terminal.draw(|frame| draw(frame, &mut state)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("1. e4      e5"));
+        assert!(screen.contains("Nf3        1.5000"));
+        assert!(screen.contains("No clock"));
+        assert_eq!(state.board_area, Rect::new(1, 1, 26, 15));
+    }
+}
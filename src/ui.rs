@@ -1,50 +1,207 @@
-use chess::Board;
+use std::cell::Cell;
+use chess::{Board, ChessMove, Color, Piece, Square, ALL_FILES, ALL_RANKS};
+use crate::clock::Clock;
 
 pub trait UI {
-    fn update(&self, board: &Board);
+    /// Show the position after `moves`, the game so far
+    fn update(&self, board: &Board, moves: &[ChessMove]);
+
+    /// Show both clocks after a move
+    fn show_clock(&self, clock: &Clock) {
+        println!("{}", clock);
+    }
+
+    fn message(&self, text: &str) {
+        println!("{}", text);
+    }
+
+    /// Hold the final position until the player is done with it
+    fn game_over(&self, _result: &str) {}
 }
 
-pub struct ConsoleUI {
+/// How the console board is drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Style {
+    /// Letters and dots, as in FEN
+    #[default]
+    Plain,
+    /// Chess glyphs with rank and file labels
+    Unicode,
+    /// Glyphs on ANSI coloured squares, highlighting the last move and checks
+    Colour,
+}
+
+// 256 colour palette indices for the squares
+pub(crate) const LIGHT: u8 = 180;
+pub(crate) const DARK: u8 = 137;
+pub(crate) const LAST_MOVE: u8 = 143;
+pub(crate) const CHECK: u8 = 167;
 
+#[derive(Default)]
+pub struct ConsoleUI {
+    pub style: Style,
+    /// Draw the board from Black's side
+    pub flipped: Cell<bool>,
+    last_move: Cell<Option<ChessMove>>,
 }
 
-impl UI for ConsoleUI {
-    fn update(&self, board: &Board) {
-        let fen = board.to_string();
-        let mut printed: u32 = 0;
-        for row in fen.split("/") {
-            let mut output = "".to_owned();
-            for c in row.chars() {
-                if printed == 64 {
-                    break;
-                }
-                if c.is_ascii_digit() {
-                    let num = c.to_digit(10).expect("All digits are less than 8");
-                    output.push_str(&". ".repeat(num as usize));
-                    printed += num;
-                }
-                else {
-                    output.push(c);
-                    output.push(' ');
-                    printed += 1;
+impl ConsoleUI {
+    pub fn new(style: Style, flipped: bool) -> ConsoleUI {
+        ConsoleUI { style, flipped: Cell::new(flipped), last_move: Cell::new(None) }
+    }
+
+    /// Turn the board around and draw it again
+    pub fn flip(&self, board: &Board) {
+        self.flipped.set(!self.flipped.get());
+        println!("{}", self.render(board, self.last_move.get()));
+        println!();
+    }
+
+    /// The board as text, top rank first
+    pub fn render(&self, board: &Board, last_move: Option<ChessMove>) -> String {
+        let flipped = self.flipped.get();
+        let mut ranks = ALL_RANKS.to_vec();
+        let mut files = ALL_FILES.to_vec();
+        if flipped {
+            files.reverse();
+        } else {
+            ranks.reverse();
+        }
+        let checked = match board.checkers().popcnt() {
+            0 => None,
+            _ => Some(board.king_square(board.side_to_move())),
+        };
+        let mut output = String::new();
+        for &rank in &ranks {
+            if self.style != Style::Plain {
+                output.push_str(&format!("{} ", rank.to_index() + 1));
+            }
+            for &file in &files {
+                let square = Square::make_square(rank, file);
+                let piece = board.piece_on(square).zip(board.color_on(square));
+                match self.style {
+                    Style::Plain => {
+                        output.push(piece.map_or('.', |(piece, color)| letter(piece, color)));
+                        output.push(' ');
+                    },
+                    Style::Unicode => {
+                        output.push(piece.map_or('·', |(piece, color)| glyph(piece, color)));
+                        output.push(' ');
+                    },
+                    Style::Colour => {
+                        let background = if Some(square) == checked {
+                            CHECK
+                        } else if last_move.is_some_and(|m| m.get_source() == square || m.get_dest() == square) {
+                            LAST_MOVE
+                        } else if (rank.to_index() + file.to_index()) % 2 == 1 {
+                            LIGHT
+                        } else {
+                            DARK
+                        };
+                        // solid glyphs for both sides, told apart by colour
+                        let (foreground, text) = match piece {
+                            Some((piece, color)) => (if color == Color::White { 231 } else { 16 }, glyph(piece, Color::Black)),
+                            None => (16, ' '),
+                        };
+                        output.push_str(&format!("\x1b[48;5;{};38;5;{}m {} ", background, foreground, text));
+                    },
                 }
             }
-            println!("{}", output);
+            if self.style == Style::Colour {
+                output.push_str("\x1b[0m");
+            }
+            output.push('\n');
         }
+        match self.style {
+            Style::Plain => {},
+            Style::Unicode => output.push_str(&labels(&files, 2, 2)),
+            Style::Colour => output.push_str(&labels(&files, 3, 3)),
+        }
+        output
+    }
+}
+
+impl UI for ConsoleUI {
+    fn update(&self, board: &Board, moves: &[ChessMove]) {
+        let last_move = moves.last().copied();
+        self.last_move.set(last_move);
+        println!("{}", self.render(board, last_move));
         println!();
-        println!();
     }
 }
 
+fn letter(piece: Piece, color: Color) -> char {
+    let letter = piece.to_string(color);
+    letter.chars().next().expect("Every piece has a letter")
+}
+
+pub(crate) fn glyph(piece: Piece, color: Color) -> char {
+    let glyphs = match color {
+        Color::White => ['♙', '♘', '♗', '♖', '♕', '♔'],
+        Color::Black => ['♟', '♞', '♝', '♜', '♛', '♚'],
+    };
+    glyphs[piece.to_index()]
+}
+
+/// File letters under the board, for squares `width` characters wide after a rank label
+fn labels(files: &[chess::File], indent: usize, width: usize) -> String {
+    let mut output = " ".repeat(indent);
+    for file in files {
+        output.push((b'a' + file.to_index() as u8) as char);
+        output.push_str(&" ".repeat(width - 1));
+    }
+    output.trim_end().to_string() + "\n"
+}
+
 
 #[cfg(test)]
 mod test {
-    use chess::Board;
-    use crate::ui::{ConsoleUI, UI};
+    use std::str::FromStr;
+    use chess::{Board, ChessMove, Square};
+    use crate::ui::{ConsoleUI, Style, UI};
 
     #[test]
     fn print_default() {
-        let ui = ConsoleUI {};
-        ui.update(&Board::default());
+        let ui = ConsoleUI::default();
+        ui.update(&Board::default(), &[]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn flipped() {
+        let board = Board::default();
+        let ui = ConsoleUI::default();
+        let rows: Vec<String> = ui.render(&board, None).lines().map(String::from).collect();
+        assert_eq!(rows[0], "r n b q k b n r ");
+        assert_eq!(rows[7], "R N B Q K B N R ");
+        ui.flip(&board);
+        let rows: Vec<String> = ui.render(&board, None).lines().map(String::from).collect();
+        assert_eq!(rows[0], "R N B K Q B N R ");
+        assert_eq!(rows[6], "p p p p p p p p ");
+    }
+
+    #[test]
+    fn unicode() {
+        let ui = ConsoleUI::new(Style::Unicode, false);
+        let rows: Vec<String> = ui.render(&Board::default(), None).lines().map(String::from).collect();
+        assert_eq!(rows[0], "8 ♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜ ");
+        assert_eq!(rows[4], "4 · · · · · · · · ");
+        assert_eq!(rows[8], "  a b c d e f g h");
+        let flipped = ConsoleUI::new(Style::Unicode, true);
+        let rows: Vec<String> = flipped.render(&Board::default(), None).lines().map(String::from).collect();
+        assert_eq!(rows[0], "1 ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖ ");
+        assert_eq!(rows[8], "  h g f e d c b a");
+    }
+
+    #[test]
+    fn highlights() {
+        let ui = ConsoleUI::new(Style::Colour, false);
+        let e4 = ChessMove::new(Square::E2, Square::E4, None);
+        let board = Board::default().make_move_new(e4);
+        let rendered = ui.render(&board, Some(e4));
+        assert_eq!(rendered.matches("48;5;143").count(), 2);
+        assert!(!rendered.contains("48;5;167"));
+        let check = Board::from_str("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").unwrap();
+        assert_eq!(ui.render(&check, None).matches("48;5;167").count(), 1);
+        assert_eq!(ui.render(&check, None).lines().last().unwrap(), "   a  b  c  d  e  f  g  h");
+    }
+}
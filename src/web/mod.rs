@@ -0,0 +1,432 @@
+use std::fs::{create_dir_all, OpenOptions};
+use std::path::Path;
+use chess::{Board, ChessMove, Color, Game, GameResult, MoveGen};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response};
+use crate::adjudication::{Adjudication, Termination};
+use crate::arena::{check_game, request_move, resources_dir, GameRecord, Outcome, Turn};
+use crate::baseline::BENCHMARK_DEPTH;
+use crate::pgn::{result, san, write_game};
+use crate::player::{parse_move, Player};
+use crate::spec::PlayerSpec;
+use crate::time_manager::SearchLimits;
+
+const INDEX: &str = include_str!("index.html");
+
+/// Reply to one request: status, content type and body
+type Reply = (u16, &'static str, String);
+
+/// A game in the browser. Sides without a player are played by the person at the browser.
+struct WebGame {
+    white_name: String,
+    black_name: String,
+    white: Option<Box<dyn Player>>,
+    black: Option<Box<dyn Player>>,
+    game: Game,
+    moves: Vec<ChessMove>,
+    draw_offer: Option<Color>,
+    over: Option<(Outcome, Termination)>,
+}
+
+/// Game state sent to the browser
+#[derive(Serialize)]
+struct GameView {
+    id: usize,
+    white: String,
+    black: String,
+    fen: String,
+    moves: Vec<String>,
+    last: Option<String>,
+    /// Moves the browser may make, empty while a bot is to move or after the game
+    legal: Vec<String>,
+    result: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NewGame {
+    white: String,
+    black: String,
+}
+
+#[derive(Deserialize)]
+struct MoveRequest {
+    #[serde(rename = "move")]
+    chess_move: String,
+}
+
+/// Everything the server knows: games in progress and the directory holding
+/// models, saved games and training logs
+pub struct WebServer {
+    root: String,
+    games: Vec<WebGame>,
+    rules: Adjudication,
+}
+
+impl WebServer {
+    pub fn new(root: &str) -> WebServer {
+        WebServer { root: root.trim_end_matches('/').to_string(), games: Vec::new(), rules: Adjudication::default() }
+    }
+
+    pub fn handle(&mut self, method: &Method, url: &str, body: &str) -> Reply {
+        let path = url.split('?').next().unwrap_or("");
+        let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, parts.as_slice()) {
+            (Method::Get, [""]) => (200, "text/html; charset=utf-8", INDEX.to_string()),
+            (Method::Get, ["api", "players"]) => json_reply(&json!({ "players": self.players() })),
+            (Method::Post, ["api", "games"]) => match serde_json::from_str::<NewGame>(body) {
+                Ok(new_game) => match self.start(&new_game.white, &new_game.black) {
+                    Ok(id) => json_reply(&self.view(id)),
+                    Err(e) => error(400, &e),
+                },
+                Err(e) => error(400, &e.to_string()),
+            },
+            (Method::Get, ["api", "games", id]) => match self.game_id(id) {
+                Some(id) => json_reply(&self.view(id)),
+                None => error(404, "No such game"),
+            },
+            (Method::Post, ["api", "games", id, "move"]) => {
+                let Some(id) = self.game_id(id) else { return error(404, "No such game") };
+                let text = match serde_json::from_str::<MoveRequest>(body) {
+                    Ok(request) => request.chess_move,
+                    Err(e) => return error(400, &e.to_string()),
+                };
+                match self.play(id, &text) {
+                    Ok(()) => json_reply(&self.view(id)),
+                    Err(e) => error(400, &e),
+                }
+            },
+            (Method::Post, ["api", "games", id, "resign"]) => {
+                let Some(id) = self.game_id(id) else { return error(404, "No such game") };
+                let side = self.games[id].game.side_to_move();
+                if self.games[id].over.is_none() && self.is_human(id, side) {
+                    self.finish(id, Outcome::win_for(!side), Termination::Resignation);
+                }
+                json_reply(&self.view(id))
+            },
+            (Method::Get, ["api", "saved"]) => json_reply(&json!({ "files": self.find("**/*.pgn") })),
+            (Method::Get, ["api", "saved", file @ ..]) => match self.read(&file.join("/"), "pgn") {
+                Some(text) => (200, "text/plain; charset=utf-8", text),
+                None => error(404, "No such game file"),
+            },
+            (Method::Get, ["api", "runs"]) => {
+                let runs: Vec<String> = self.find("logs/*/champs.csv").iter()
+                    .filter_map(|file| file.strip_prefix("logs/")?.strip_suffix("/champs.csv").map(String::from))
+                    .collect();
+                json_reply(&json!({ "runs": runs }))
+            },
+            (Method::Get, ["api", "runs", run]) => match self.read(&format!("logs/{}/champs.csv", run), "csv") {
+                Some(text) => json_reply(&champions(&text)),
+                None => error(404, "No such run"),
+            },
+            _ => error(404, "Not found"),
+        }
+    }
+
+    /// Everything that can sit at the board: the browser, the baseline bots, or any saved model
+    fn players(&self) -> Vec<String> {
+        let mut players: Vec<String> = ["human", "random", "greedy", "material"].iter().map(|p| p.to_string()).collect();
+        players.push(format!("minimax:{}", BENCHMARK_DEPTH));
+        players.extend(self.find("**/*.safetensors").iter().map(|file| format!("{}/{}", self.root, file)));
+        players
+    }
+
+    /// Files under the root matching `pattern`, relative to it
+    fn find(&self, pattern: &str) -> Vec<String> {
+        let mut files: Vec<String> = glob::glob(&format!("{}/{}", self.root, pattern))
+            .expect("Bad file pattern")
+            .filter_map(Result::ok)
+            .filter_map(|path| Some(path.strip_prefix(&self.root).ok()?.to_string_lossy().to_string()))
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// A file under the root with the given extension, refusing paths that climb out of it
+    fn read(&self, file: &str, extension: &str) -> Option<String> {
+        let path = Path::new(file);
+        let inside = path.components().all(|part| matches!(part, std::path::Component::Normal(_)));
+        if !inside || path.extension()? != extension {
+            return None;
+        }
+        std::fs::read_to_string(Path::new(&self.root).join(path)).ok()
+    }
+
+    fn game_id(&self, id: &str) -> Option<usize> {
+        id.parse().ok().filter(|&id| id < self.games.len())
+    }
+
+    fn is_human(&self, id: usize, side: Color) -> bool {
+        let game = &self.games[id];
+        match side {
+            Color::White => game.white.is_none(),
+            Color::Black => game.black.is_none(),
+        }
+    }
+
+    /// Seat both players, failing on a bad spec or a model that won't load
+    fn start(&mut self, white: &str, black: &str) -> Result<usize, String> {
+        let seat = |spec: &str| match spec {
+            "human" => Ok(None),
+            spec => spec.parse::<PlayerSpec>().and_then(|spec| spec.build()).map(Some),
+        };
+        let (white_player, black_player) = (seat(white)?, seat(black)?);
+        self.games.push(WebGame {
+            white_name: white.to_string(),
+            black_name: black.to_string(),
+            white: white_player,
+            black: black_player,
+            game: Game::new(),
+            moves: Vec::new(),
+            draw_offer: None,
+            over: None,
+        });
+        let id = self.games.len() - 1;
+        self.advance(id);
+        Ok(id)
+    }
+
+    /// Make the browser's move and let any bots reply
+    fn play(&mut self, id: usize, text: &str) -> Result<(), String> {
+        let board = self.games[id].game.current_position();
+        if self.games[id].over.is_some() {
+            return Err("The game is over".to_string());
+        }
+        if !self.is_human(id, board.side_to_move()) {
+            return Err("It's not your move".to_string());
+        }
+        let chess_move = parse_move(&board, text).ok_or(format!("{} isn't a legal move", text))?;
+        self.make_move(id, chess_move);
+        self.advance(id);
+        Ok(())
+    }
+
+    fn make_move(&mut self, id: usize, chess_move: ChessMove) {
+        let web_game = &mut self.games[id];
+        web_game.game.make_move(chess_move);
+        web_game.moves.push(chess_move);
+        if check_game(&mut web_game.game) {
+            let result = web_game.game.result().expect("check_game found a result");
+            let termination = match result {
+                GameResult::WhiteCheckmates | GameResult::BlackCheckmates => Termination::Checkmate,
+                GameResult::Stalemate => Termination::Stalemate,
+                _ => Termination::DrawClaim,
+            };
+            self.finish(id, Outcome::from(result), termination);
+        } else if web_game.moves.len() >= self.rules.max_plies {
+            self.finish(id, Outcome::Draw, Termination::MaxLength);
+        }
+    }
+
+    /// Play bot moves until the browser is to move or the game ends
+    fn advance(&mut self, id: usize) {
+        while self.games[id].over.is_none() {
+            let web_game = &mut self.games[id];
+            let board = web_game.game.current_position();
+            let player = match board.side_to_move() {
+                Color::White => web_game.white.as_deref(),
+                Color::Black => web_game.black.as_deref(),
+            };
+            let Some(player) = player else { return };
+            match request_move(player, &board, None, &SearchLimits::default(), &mut web_game.draw_offer) {
+                Turn::Move(chess_move) => self.make_move(id, chess_move),
+                Turn::TakeBack => self.finish(id, Outcome::win_for(!board.side_to_move()), Termination::Abandoned),
+                Turn::Over(outcome, termination) => self.finish(id, outcome, termination),
+            }
+        }
+    }
+
+    /// Record the result and save the game with the others played in the browser
+    fn finish(&mut self, id: usize, outcome: Outcome, termination: Termination) {
+        let web_game = &mut self.games[id];
+        web_game.over = Some((outcome, termination));
+        let dir = format!("{}/web", self.root);
+        create_dir_all(&dir).expect("Error creating web games directory");
+        let mut file = OpenOptions::new().create(true).append(true).open(format!("{}/games.pgn", dir))
+            .expect("Couldn't open web games file");
+        let record = GameRecord { moves: web_game.moves.clone(), outcome, termination };
+        let tags = [
+            ("Event", "chessers serve".to_string()),
+            ("Date", chrono::Local::now().format("%Y.%m.%d").to_string()),
+            ("White", web_game.white_name.clone()),
+            ("Black", web_game.black_name.clone()),
+        ];
+        write_game(&mut file, &tags, &record).expect("Couldn't save web game");
+    }
+
+    fn view(&self, id: usize) -> GameView {
+        let web_game = &self.games[id];
+        let board = web_game.game.current_position();
+        let mut history = Board::default();
+        let moves = web_game.moves.iter()
+            .map(|&chess_move| {
+                let text = san(&history, chess_move);
+                history = history.make_move_new(chess_move);
+                text
+            })
+            .collect();
+        let legal = if web_game.over.is_none() && self.is_human(id, board.side_to_move()) {
+            MoveGen::new_legal(&board).map(|chess_move| chess_move.to_string()).collect()
+        } else {
+            Vec::new()
+        };
+        GameView {
+            id,
+            white: web_game.white_name.clone(),
+            black: web_game.black_name.clone(),
+            fen: board.to_string(),
+            moves,
+            last: web_game.moves.last().map(ChessMove::to_string),
+            legal,
+            result: web_game.over.map(|(outcome, termination)| format!("{} by {}", result(outcome), termination)),
+        }
+    }
+}
+
+/// Champion score by epoch from a run's champs.csv
+fn champions(text: &str) -> serde_json::Value {
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let (mut epochs, mut champions, mut scores) = (Vec::new(), Vec::new(), Vec::new());
+    for row in reader.records().filter_map(Result::ok) {
+        let field = |i: usize| row.get(i).unwrap_or("").to_string();
+        epochs.push(field(0).parse::<u32>().unwrap_or_default());
+        champions.push(field(1).parse::<usize>().unwrap_or_default());
+        scores.push(field(2).parse::<f64>().unwrap_or_default());
+    }
+    json!({ "epochs": epochs, "champions": champions, "scores": scores })
+}
+
+fn json_reply<T: Serialize>(value: &T) -> Reply {
+    (200, "application/json", serde_json::to_string(value).expect("Couldn't serialise reply"))
+}
+
+fn error(status: u16, message: &str) -> Reply {
+    (status, "application/json", json!({ "error": message }).to_string())
+}
+
+fn respond(server: &mut WebServer, mut request: Request) {
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        println!("Couldn't read request: {}", e);
+        return;
+    }
+    let (status, content_type, text) = server.handle(request.method(), request.url(), &body);
+    let header = Header::from_bytes("Content-Type", content_type).expect("Bad content type");
+    let response = Response::from_string(text).with_status_code(status).with_header(header);
+    if let Err(e) = request.respond(response) {
+        println!("Couldn't send response: {}", e);
+    }
+}
+
+/// Answer requests one at a time until the process is stopped
+pub fn run(http: tiny_http::Server, root: &str) {
+    let mut server = WebServer::new(root);
+    for request in http.incoming_requests() {
+        respond(&mut server, request);
+    }
+}
+
+pub fn serve(port: u16) {
+    let http = tiny_http::Server::http(("127.0.0.1", port)).unwrap_or_else(|e| panic!("Couldn't listen on port {}: {}", port, e));
+    println!("Serving on http://127.0.0.1:{}", port);
+    run(http, &resources_dir());
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir_all, write};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use serde_json::{json, Value};
+    use tiny_http::Method;
+    use crate::web::{run, WebServer};
+
+    fn temp_root(name: &str) -> String {
+        let root = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+        root.to_string_lossy().to_string()
+    }
+
+    fn json(server: &mut WebServer, method: Method, url: &str, body: &str) -> (u16, Value) {
+        let (status, _, text) = server.handle(&method, url, body);
+        (status, serde_json::from_str(&text).unwrap())
+    }
+
+    #[test]
+    fn play_in_the_browser() {
+        let mut server = WebServer::new(&temp_root("chessers_web_play"));
+        let (status, game) = json(&mut server, Method::Post, "/api/games", r#"{"white": "human", "black": "random"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(game["legal"].as_array().unwrap().len(), 20);
+
+        let (status, game) = json(&mut server, Method::Post, "/api/games/0/move", r#"{"move": "e2e4"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(game["moves"].as_array().unwrap().len(), 2);
+        assert_eq!(game["moves"][0], "e4");
+        let (status, _) = json(&mut server, Method::Post, "/api/games/0/move", r#"{"move": "e2e4"}"#);
+        assert_eq!(status, 400);
+        let (status, _) = json(&mut server, Method::Get, "/api/games/1", "");
+        assert_eq!(status, 404);
+        let (status, reply) = json(&mut server, Method::Post, "/api/games", r#"{"white": "human", "black": "stockfish"}"#);
+        assert_eq!(status, 400);
+        assert!(reply["error"].as_str().unwrap().starts_with("Unknown player"));
+
+        // a model file that won't load is refused without bringing the server down
+        let corrupt = std::env::temp_dir().join("chessers_web_corrupt.safetensors");
+        std::fs::write(&corrupt, "not a model").unwrap();
+        let body = json!({ "white": "human", "black": corrupt.to_str().unwrap() }).to_string();
+        let (status, reply) = json(&mut server, Method::Post, "/api/games", &body);
+        assert_eq!(status, 400);
+        assert!(reply["error"].as_str().unwrap().starts_with("Couldn't read model"));
+        let (status, _) = json(&mut server, Method::Get, "/api/games/0", "");
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn saved_games() {
+        let mut server = WebServer::new(&temp_root("chessers_web_saved"));
+        json(&mut server, Method::Post, "/api/games", r#"{"white": "human", "black": "human"}"#);
+        let (_, game) = json(&mut server, Method::Post, "/api/games/0/resign", "");
+        assert_eq!(game["result"], "0-1 by resignation");
+        assert!(game["legal"].as_array().unwrap().is_empty());
+
+        let (_, saved) = json(&mut server, Method::Get, "/api/saved", "");
+        assert_eq!(saved["files"][0], "web/games.pgn");
+        let (status, _, pgn) = server.handle(&Method::Get, "/api/saved/web/games.pgn", "");
+        assert_eq!(status, 200);
+        assert!(pgn.contains("[Result \"0-1\"]"));
+        let (status, _, _) = server.handle(&Method::Get, "/api/saved/../chessers_web_play/web/games.pgn", "");
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn training_runs() {
+        let root = temp_root("chessers_web_runs");
+        create_dir_all(format!("{}/logs/2024_10_01", root)).unwrap();
+        write(format!("{}/logs/2024_10_01/champs.csv", root), "Epoch,Champ index,Score\n0,3,1.5\n1,0,2\n").unwrap();
+        let mut server = WebServer::new(&root);
+        let (_, runs) = json(&mut server, Method::Get, "/api/runs", "");
+        assert_eq!(runs["runs"][0], "2024_10_01");
+        let (_, run) = json(&mut server, Method::Get, "/api/runs/2024_10_01", "");
+        assert_eq!(run["champions"], serde_json::json!([3, 0]));
+        assert_eq!(run["scores"], serde_json::json!([1.5, 2.0]));
+        let (_, players) = json(&mut server, Method::Get, "/api/players", "");
+        assert_eq!(players["players"], serde_json::json!(["human", "random", "greedy", "material", "minimax:2"]));
+    }
+
+    #[test]
+    fn over_localhost() {
+        let http = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let port = http.server_addr().to_ip().unwrap().port();
+        let root = temp_root("chessers_web_localhost");
+        std::thread::spawn(move || run(http, &root));
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("<title>Chessers</title>"));
+    }
+}
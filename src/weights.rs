@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use candle_core::Tensor;
+use crate::nn::ChessNet;
+
+/// Weights smaller than this count as zero for sparsity
+const ZERO: f64 = 1e-6;
+
+/// Summary of one tensor's values
+#[derive(Clone, Debug, PartialEq)]
+pub struct TensorStats {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub count: usize,
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Fraction of values that are zero
+    pub sparsity: f64,
+}
+
+impl TensorStats {
+    pub fn new(name: &str, tensor: &Tensor) -> TensorStats {
+        let values = values(tensor);
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+        TensorStats {
+            name: name.to_string(),
+            shape: tensor.dims().to_vec(),
+            count,
+            mean,
+            std: variance.sqrt(),
+            min: values.iter().cloned().fold(f64::INFINITY, f64::min),
+            max: values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            sparsity: values.iter().filter(|v| v.abs() < ZERO).count() as f64 / count as f64,
+        }
+    }
+}
+
+/// How far one tensor moved between two models
+#[derive(Clone, Debug, PartialEq)]
+pub struct TensorDiff {
+    pub name: String,
+    /// Euclidean distance between the two tensors
+    pub distance: f64,
+    /// Distance as a fraction of the first tensor's norm
+    pub relative: f64,
+    pub max_change: f64,
+    /// Fraction of weights that changed at all
+    pub changed: f64,
+}
+
+/// Every tensor's statistics, sorted by name
+pub fn info(model: &ChessNet) -> Vec<TensorStats> {
+    model.named_tensors().iter()
+        .map(|(name, tensor)| TensorStats::new(name, tensor))
+        .collect()
+}
+
+/// Per-tensor distance between two models, plus the names only one of them has
+pub fn diff(a: &ChessNet, b: &ChessNet) -> (Vec<TensorDiff>, Vec<String>) {
+    let first: BTreeMap<String, Tensor> = a.named_tensors().into_iter().collect();
+    let mut second: BTreeMap<String, Tensor> = b.named_tensors().into_iter().collect();
+    let mut diffs = Vec::new();
+    let mut unmatched = Vec::new();
+    for (name, tensor) in first {
+        let Some(other) = second.remove(&name) else {
+            unmatched.push(name);
+            continue;
+        };
+        if tensor.dims() != other.dims() {
+            unmatched.push(name);
+            continue;
+        }
+        let (x, y) = (values(&tensor), values(&other));
+        let changes: Vec<f64> = x.iter().zip(&y).map(|(x, y)| y - x).collect();
+        let distance = changes.iter().map(|c| c * c).sum::<f64>().sqrt();
+        let norm = x.iter().map(|v| v * v).sum::<f64>().sqrt();
+        diffs.push(TensorDiff {
+            name,
+            distance,
+            relative: if norm > 0. { distance / norm } else { 0. },
+            max_change: changes.iter().fold(0., |max: f64, c| max.max(c.abs())),
+            changed: changes.iter().filter(|c| **c != 0.).count() as f64 / changes.len() as f64,
+        });
+    }
+    unmatched.extend(second.into_keys());
+    (diffs, unmatched)
+}
+
+/// Euclidean distance between all the weights two models share
+pub fn distance(a: &ChessNet, b: &ChessNet) -> f64 {
+    diff(a, b).0.iter().map(|d| d.distance.powi(2)).sum::<f64>().sqrt()
+}
+
+fn values(tensor: &Tensor) -> Vec<f64> {
+    tensor.flatten_all()
+        .and_then(|flat| flat.to_dtype(candle_core::DType::F64))
+        .and_then(|flat| flat.to_vec1())
+        .expect("Couldn't read tensor values")
+}
+
+pub fn print_info(stats: &[TensorStats]) {
+    println!("Architecture: {}", ChessNet::ARCHITECTURE);
+    let total: usize = stats.iter().map(|s| s.count).sum();
+    println!("Parameters: {}", total);
+    println!();
+    println!("{:<10} {:<14} {:>6} {:>9} {:>9} {:>9} {:>9} {:>8}", "Tensor", "Shape", "Count", "Mean", "Std", "Min", "Max", "Sparsity");
+    for s in stats {
+        println!("{:<10} {:<14} {:>6} {:>9.4} {:>9.4} {:>9.4} {:>9.4} {:>7.1}%",
+                 s.name, format!("{:?}", s.shape), s.count, s.mean, s.std, s.min, s.max, 100. * s.sparsity);
+    }
+}
+
+pub fn print_diff(diffs: &[TensorDiff], unmatched: &[String]) {
+    println!("{:<10} {:>10} {:>9} {:>10} {:>8}", "Tensor", "Distance", "Relative", "Max change", "Changed");
+    for d in diffs {
+        println!("{:<10} {:>10.4} {:>8.1}% {:>10.4} {:>7.1}%", d.name, d.distance, 100. * d.relative, d.max_change, 100. * d.changed);
+    }
+    let total = diffs.iter().map(|d| d.distance.powi(2)).sum::<f64>().sqrt();
+    println!("{:<10} {:>10.4}", "Total", total);
+    for name in unmatched {
+        println!("{} is only in one model, or has a different shape", name);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use candle_core::{Device, Tensor};
+    use candle_nn::VarMap;
+    use crate::arena::TrainConfig;
+    use crate::nn::ChessNet;
+    use crate::weights::{diff, info, TensorStats};
+
+    #[test]
+    fn stats() {
+        let tensor = Tensor::new(&[[0f64, 2.], [-2., 4.]], &Device::Cpu).unwrap();
+        let stats = TensorStats::new("w", &tensor);
+        assert_eq!(stats.shape, vec![2, 2]);
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.mean, 1.);
+        assert!((stats.std - 5f64.sqrt()).abs() < 1e-12);
+        assert_eq!((stats.min, stats.max), (-2., 4.));
+        assert_eq!(stats.sparsity, 0.25);
+    }
+
+    #[test]
+    fn every_tensor_counted() {
+        let stats = info(&ChessNet::new(VarMap::new()));
+        let names: Vec<&str> = stats.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["c1.bias", "c1.weight", "t1.bias", "t1.weight"]);
+        assert_eq!(stats.iter().map(|s| s.count).sum::<usize>(), 2 + 2 * 6 * 9 + 2 + 2 * 2 * 9);
+    }
+
+    #[test]
+    fn merge_moves_weights() {
+        let a = ChessNet::new(VarMap::new());
+        let (same, unmatched) = diff(&a, &a);
+        assert!(unmatched.is_empty());
+        assert!(same.iter().all(|d| d.distance == 0. && d.changed == 0.));
+
+        let b = ChessNet::new(VarMap::new());
+        let child = a.merge(&b, [1., 0.], &TrainConfig::default());
+        let (moved, _) = diff(&a, &child);
+        assert_eq!(moved.len(), 4);
+        assert!(moved.iter().any(|d| d.distance > 0.));
+        assert!(moved.iter().all(|d| d.changed <= 1. && d.relative >= 0.));
+    }
+}